@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Error, Result};
+use console::{measure_text_width, pad_str, Alignment};
+
+use crate::api::{
+    get_dict_result, get_jyutping_result, is_chinese_word, MoedictDictionary, MoedictRawResult,
+    WordList,
+};
+use crate::cantonese::CantoneseRomanization;
+use crate::client::MeowdictClient;
+
+/// Prefix of readings with several candidates, which are never narrowed down
+const AMBIGUOUS_MARK: &str = "?";
+const LOOKUP_CHUNK_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum AnnotationFormat {
+    Html,
+    Markdown,
+    #[default]
+    Interlinear,
+}
+
+impl FromStr for AnnotationFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "html" => Ok(AnnotationFormat::Html),
+            "markdown" => Ok(AnnotationFormat::Markdown),
+            "interlinear" => Ok(AnnotationFormat::Interlinear),
+            _ => Err(anyhow!("Unsupport annotation format: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum AnnotationReading {
+    #[default]
+    Pinyin,
+    Zhuyin,
+    Jyutping,
+}
+
+impl FromStr for AnnotationReading {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pinyin" => Ok(AnnotationReading::Pinyin),
+            "zhuyin" => Ok(AnnotationReading::Zhuyin),
+            "jyutping" => Ok(AnnotationReading::Jyutping),
+            _ => Err(anyhow!("Unsupport annotation reading: {}", s)),
+        }
+    }
+}
+
+/// A piece of text with its candidate readings, none for punctuation and
+/// unknown words
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub text: String,
+    pub readings: Vec<String>,
+}
+
+impl Annotation {
+    fn plain(text: &str) -> Self {
+        Annotation {
+            text: text.to_string(),
+            readings: Vec::new(),
+        }
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.readings.len() > 1
+    }
+
+    fn reading_str(&self) -> Option<String> {
+        if self.readings.is_empty() {
+            return None;
+        }
+        let readings = self.readings.join(" / ");
+
+        Some(if self.is_ambiguous() {
+            format!("{}{}", AMBIGUOUS_MARK, readings)
+        } else {
+            readings
+        })
+    }
+}
+
+/// Segment every line of `text` and attach readings to its words. Words
+/// moedict does not know are annotated character by character.
+pub async fn annotate_text(
+    client: &MeowdictClient,
+    text: &str,
+    dict: MoedictDictionary,
+    reading: AnnotationReading,
+    romanization: CantoneseRomanization,
+) -> Result<Vec<Vec<Annotation>>> {
+    let word_list = WordList::new(client, dict).await?;
+    let lines = text
+        .lines()
+        .map(|line| {
+            let mut segments = Vec::new();
+            for (index, token) in line.split_whitespace().enumerate() {
+                if index > 0 {
+                    segments.push(" ".to_string());
+                }
+                segments.extend(word_list.segment(token));
+            }
+
+            segments
+        })
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let words = lines
+        .iter()
+        .flatten()
+        .filter(|x| is_chinese_word(x) && seen.insert(*x))
+        .cloned()
+        .collect::<Vec<_>>();
+    let annotations = match reading {
+        AnnotationReading::Pinyin => moedict_annotations(client, &words, dict, false).await,
+        AnnotationReading::Zhuyin => moedict_annotations(client, &words, dict, true).await,
+        AnnotationReading::Jyutping => jyutping_annotations(client, &words, romanization).await?,
+    };
+
+    Ok(lines
+        .into_iter()
+        .map(|line| {
+            line.iter()
+                .flat_map(|x| {
+                    annotations
+                        .get(x)
+                        .cloned()
+                        .unwrap_or_else(|| vec![Annotation::plain(x)])
+                })
+                .collect()
+        })
+        .collect())
+}
+
+async fn moedict_annotations(
+    client: &MeowdictClient,
+    words: &[String],
+    dict: MoedictDictionary,
+    zhuyin: bool,
+) -> HashMap<String, Vec<Annotation>> {
+    let mut annotations = HashMap::new();
+    let mut missing = Vec::new();
+    for (word, readings) in moedict_readings(client, words, dict, zhuyin).await {
+        if !readings.is_empty() {
+            let annotation = Annotation {
+                text: word.clone(),
+                readings,
+            };
+            annotations.insert(word, vec![annotation]);
+        } else if word.chars().count() > 1 {
+            missing.push(word);
+        }
+    }
+    let mut seen = HashSet::new();
+    let chars = missing
+        .iter()
+        .flat_map(|x| x.chars())
+        .map(String::from)
+        .filter(|x| seen.insert(x.clone()))
+        .collect::<Vec<_>>();
+    let char_readings = moedict_readings(client, &chars, dict, zhuyin)
+        .await
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    for word in missing {
+        let annotation = word
+            .chars()
+            .map(|x| {
+                let text = x.to_string();
+                let readings = char_readings.get(&text).cloned().unwrap_or_default();
+                Annotation { text, readings }
+            })
+            .collect();
+        annotations.insert(word, annotation);
+    }
+
+    annotations
+}
+
+/// Distinct readings of every heteronym, empty when the lookup failed
+async fn moedict_readings(
+    client: &MeowdictClient,
+    words: &[String],
+    dict: MoedictDictionary,
+    zhuyin: bool,
+) -> Vec<(String, Vec<String>)> {
+    let mut result = Vec::new();
+    for chunk in words.chunks(LOOKUP_CHUNK_SIZE) {
+        let entries = get_dict_result(client, chunk, dict).await;
+        for (word, entry) in chunk.iter().zip(entries) {
+            let readings = entry
+                .map(|x| heteronym_readings(&x, zhuyin))
+                .unwrap_or_default();
+            result.push((word.clone(), readings));
+        }
+    }
+
+    result
+}
+
+fn heteronym_readings(entry: &MoedictRawResult, zhuyin: bool) -> Vec<String> {
+    let mut readings = Vec::new();
+    for heteronym in entry.heteronyms.iter().flatten() {
+        let reading = if zhuyin {
+            &heteronym.bopomofo
+        } else {
+            &heteronym.pinyin
+        };
+        if let Some(reading) = reading {
+            if !readings.contains(reading) {
+                readings.push(reading.clone());
+            }
+        }
+    }
+
+    readings
+}
+
+/// Composed words.hk readings keep the segments they were composed from
+async fn jyutping_annotations(
+    client: &MeowdictClient,
+    words: &[String],
+    romanization: CantoneseRomanization,
+) -> Result<HashMap<String, Vec<Annotation>>> {
+    let mut annotations = HashMap::new();
+    for (word, result) in words.iter().zip(get_jyutping_result(client, words).await?) {
+        let result = match result {
+            Ok(result) => result.romanize(romanization),
+            Err(_) => continue,
+        };
+        let annotation = match result.segments {
+            Some(segments) => segments
+                .into_iter()
+                .map(|x| Annotation {
+                    text: x.text,
+                    readings: x.jyutping,
+                })
+                .collect(),
+            None => vec![Annotation {
+                text: result.word,
+                readings: result.jyutping.into_iter().map(|x| x.jyutping).collect(),
+            }],
+        };
+        annotations.insert(word.clone(), annotation);
+    }
+
+    Ok(annotations)
+}
+
+pub fn gen_annotation_str(lines: &[Vec<Annotation>], format: AnnotationFormat) -> String {
+    match format {
+        AnnotationFormat::Html => lines
+            .iter()
+            .map(|line| format!("<p>{}</p>", line.iter().map(html_ruby).collect::<String>()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        AnnotationFormat::Markdown => lines
+            .iter()
+            .map(|line| line.iter().map(markdown_ruby).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("  \n"),
+        AnnotationFormat::Interlinear => lines
+            .iter()
+            .map(|line| interlinear_lines(line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn html_ruby(annotation: &Annotation) -> String {
+    let text = html_escape(&annotation.text);
+    match annotation.reading_str() {
+        Some(reading) if annotation.is_ambiguous() => format!(
+            "<ruby class=\"ambiguous\">{}<rt>{}</rt></ruby>",
+            text,
+            html_escape(&reading)
+        ),
+        Some(reading) => format!("<ruby>{}<rt>{}</rt></ruby>", text, html_escape(&reading)),
+        None => text,
+    }
+}
+
+fn markdown_ruby(annotation: &Annotation) -> String {
+    match annotation.reading_str() {
+        Some(reading) => format!("{}({})", annotation.text, reading),
+        None => annotation.text.clone(),
+    }
+}
+
+/// Readings above the text, every word padded to the wider of the two
+fn interlinear_lines(line: &[Annotation]) -> String {
+    let mut readings = Vec::new();
+    let mut texts = Vec::new();
+    for annotation in line.iter().filter(|x| !x.text.trim().is_empty()) {
+        let reading = annotation.reading_str().unwrap_or_default();
+        let width = measure_text_width(&reading).max(measure_text_width(&annotation.text));
+        readings.push(pad_str(&reading, width, Alignment::Left, None).to_string());
+        texts.push(pad_str(&annotation.text, width, Alignment::Left, None).to_string());
+    }
+    let readings = readings.join(" ");
+    let texts = texts.join(" ");
+    if readings.trim().is_empty() {
+        return texts.trim_end().to_string();
+    }
+
+    format!("{}\n{}", readings.trim_end(), texts.trim_end())
+}
+
+#[test]
+fn test_annotation_str() {
+    let annotation = |text: &str, readings: &[&str]| Annotation {
+        text: text.to_string(),
+        readings: readings.iter().map(|x| x.to_string()).collect(),
+    };
+    let lines = vec![vec![
+        annotation("銀行", &["yín háng"]),
+        annotation("行", &["xíng", "háng"]),
+        annotation("，", &[]),
+    ]];
+
+    assert_eq!(
+        gen_annotation_str(&lines, AnnotationFormat::Html),
+        "<p><ruby>銀行<rt>yín háng</rt></ruby><ruby class=\"ambiguous\">行<rt>?xíng / háng</rt></ruby>，</p>"
+    );
+    assert_eq!(
+        gen_annotation_str(&lines, AnnotationFormat::Markdown),
+        "銀行(yín háng)行(?xíng / háng)，"
+    );
+    assert_eq!(
+        gen_annotation_str(&lines, AnnotationFormat::Interlinear),
+        "yín háng ?xíng / háng\n銀行     行           ，"
+    );
+    assert_eq!(
+        gen_annotation_str(
+            &[vec![annotation("OK", &[])]],
+            AnnotationFormat::Interlinear
+        ),
+        "OK"
+    );
+}
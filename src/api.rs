@@ -1,57 +1,458 @@
 use std::{
-    collections::HashMap,
-    fs::{self, create_dir_all, File},
-    io::Write,
-    path::{Path, PathBuf},
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    path::Path,
+    str::FromStr,
     time::SystemTime,
 };
 
 use anyhow::{anyhow, Error, Result};
-use futures::future;
+use futures::{future, Future};
 use indexmap::IndexMap;
-use lazy_static::lazy_static;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::cache::{
+    moedict_index_cache_path, read_json_cache, read_validators, remove_cache_file, touch_cache,
+    write_atomic, write_validators, CacheEntry, CacheLock, CacheValidator, CacheValidators,
+    EntryCache, JYUTPING_CACHE_PATH, LEGACY_JYUTPING_CACHE_PATH,
+};
+use crate::cantonese::{convert_jyutping, CantoneseRomanization};
+use crate::client::MeowdictClient;
+use crate::mandarin::{romanize, PinyinStyle};
+use crate::store::{
+    get_offline_moedict, get_offline_moedict_characters, get_offline_moedict_index,
+    write_sorted_table, SortedTable,
+};
+
+/// `def`, `quote`, `example` and `link` keep moedict's `` `字~ `` link markup,
+/// which is only stripped when serializing
 #[derive(Deserialize, Serialize, Clone)]
 pub struct MoedictDefinition {
     #[serde(rename(deserialize = "type"))]
     pub word_type: Option<String>,
-    #[serde(rename(deserialize = "q"))]
+    #[serde(
+        rename(deserialize = "q"),
+        alias = "quote",
+        serialize_with = "serialize_markup_list"
+    )]
     pub quote: Option<Vec<String>>,
-    #[serde(rename(deserialize = "e"))]
+    #[serde(
+        rename(deserialize = "e"),
+        alias = "example",
+        serialize_with = "serialize_markup_list"
+    )]
     pub example: Option<Vec<String>>,
-    #[serde(rename(deserialize = "f"))]
+    #[serde(
+        rename(deserialize = "f"),
+        alias = "def",
+        serialize_with = "serialize_markup"
+    )]
     pub def: Option<String>,
-    #[serde(rename(deserialize = "l"))]
+    #[serde(
+        rename(deserialize = "l"),
+        alias = "link",
+        serialize_with = "serialize_markup_list"
+    )]
     pub link: Option<Vec<String>>,
+    #[serde(
+        rename(deserialize = "s"),
+        alias = "synonyms",
+        default,
+        deserialize_with = "deserialize_word_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub synonyms: Option<Vec<String>>,
+    #[serde(
+        rename(deserialize = "a"),
+        alias = "antonyms",
+        default,
+        deserialize_with = "deserialize_word_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub antonyms: Option<Vec<String>>,
+    /// Linked words, in order of first appearance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+}
+
+impl MoedictDefinition {
+    fn parse_references(&mut self) {
+        let texts = self
+            .def
+            .iter()
+            .chain(self.quote.iter().flatten())
+            .chain(self.example.iter().flatten())
+            .chain(self.link.iter().flatten());
+        let mut references = Vec::new();
+        let words = self
+            .synonyms
+            .iter()
+            .chain(self.antonyms.iter())
+            .flatten()
+            .cloned();
+        for word in texts.flat_map(|x| parse_link_markup(x)).chain(words) {
+            if !references.contains(&word) {
+                references.push(word);
+            }
+        }
+        self.references = references;
+    }
+}
+
+/// Words marked as `` `字~ `` in `s`, in order
+pub fn parse_link_markup(s: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut current: Option<String> = None;
+    for c in s.chars() {
+        match (c, &mut current) {
+            ('`', _) => current = Some(String::new()),
+            ('~', Some(_)) => links.extend(current.take().filter(|x| !x.is_empty())),
+            (c, Some(word)) => word.push(c),
+            _ => (),
+        }
+    }
+
+    links
+}
+
+pub fn strip_link_markup(s: &str) -> String {
+    s.replace(['`', '~'], "")
+}
+
+/// Moedict writes synonyms and antonyms as one comma separated string
+fn deserialize_word_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum WordList {
+        List(Vec<String>),
+        Text(String),
+    }
+
+    Ok(match Option::<WordList>::deserialize(deserializer)? {
+        Some(WordList::List(words)) => Some(words),
+        Some(WordList::Text(words)) => Some(
+            words
+                .split([',', '，', '、'])
+                .map(|x| strip_link_markup(x.trim()))
+                .filter(|x| !x.is_empty())
+                .collect(),
+        ),
+        None => None,
+    })
+}
+
+fn serialize_markup<S: Serializer>(s: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    s.as_deref().map(strip_link_markup).serialize(serializer)
+}
+
+fn serialize_markup_list<S: Serializer>(
+    s: &Option<Vec<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    s.as_ref()
+        .map(|x| x.iter().map(|x| strip_link_markup(x)).collect::<Vec<_>>())
+        .serialize(serializer)
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct MoedictHeteronym {
-    #[serde(rename(deserialize = "p"))]
+    #[serde(rename(deserialize = "p"), alias = "pinyin")]
     pub pinyin: Option<String>,
-    #[serde(rename(deserialize = "b"))]
+    #[serde(rename(deserialize = "b"), alias = "bopomofo")]
     pub bopomofo: Option<String>,
-    #[serde(rename(deserialize = "d"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading_kind: Option<ReadingKind>,
+    #[serde(rename(deserialize = "d"), alias = "definitions")]
     pub definitions: Option<Vec<MoedictDefinition>>,
+    #[serde(
+        rename(deserialize = "T"),
+        alias = "trs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tailo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub hakka: Option<Vec<HakkaReading>>,
+    /// Idioms list synonyms and antonyms for the whole heteronym
+    #[serde(
+        rename(deserialize = "s"),
+        alias = "synonyms",
+        default,
+        deserialize_with = "deserialize_word_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub synonyms: Option<Vec<String>>,
+    #[serde(
+        rename(deserialize = "a"),
+        alias = "antonyms",
+        default,
+        deserialize_with = "deserialize_word_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub antonyms: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct HakkaReading {
+    pub dialect: String,
+    pub romanization: String,
+}
+
+/// Moedict marks heteronyms as `（語音）wǒ`, `（讀音）ě` or `（又音）…`
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadingKind {
+    Colloquial,
+    Literary,
+    Alternative,
+}
+
+impl ReadingKind {
+    const ALL: [ReadingKind; 3] = [
+        ReadingKind::Colloquial,
+        ReadingKind::Literary,
+        ReadingKind::Alternative,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReadingKind::Colloquial => "語音",
+            ReadingKind::Literary => "讀音",
+            ReadingKind::Alternative => "又音",
+        }
+    }
+
+    /// Split a leading `（語音）` style label off `s`
+    fn strip_label(s: &str) -> (Option<ReadingKind>, &str) {
+        for kind in ReadingKind::ALL {
+            if let Some(rest) = s
+                .strip_prefix('（')
+                .and_then(|x| x.strip_prefix(kind.label()))
+                .and_then(|x| x.strip_prefix('）'))
+            {
+                return (Some(kind), rest.trim_start());
+            }
+        }
+
+        (None, s)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MoedictDictionary {
+    #[default]
+    Mandarin,
+    Taiwanese,
+    Hakka,
+    CrossStrait,
+}
+
+impl MoedictDictionary {
+    pub const ALL: [MoedictDictionary; 4] = [
+        MoedictDictionary::Mandarin,
+        MoedictDictionary::Taiwanese,
+        MoedictDictionary::Hakka,
+        MoedictDictionary::CrossStrait,
+    ];
+
+    /// Page of `word` on the moedict website, percent encoded
+    pub fn web_url(&self, word: &str) -> String {
+        let prefix = match self {
+            MoedictDictionary::Mandarin => "",
+            MoedictDictionary::Taiwanese => "'",
+            MoedictDictionary::Hakka => ":",
+            MoedictDictionary::CrossStrait => "~",
+        };
+        let url = format!("{}/{}{}", MOEDICT_WEB_URL, prefix, word);
+
+        reqwest::Url::parse(&url)
+            .map(|x| x.to_string())
+            .unwrap_or(url)
+    }
+
+    pub fn path(&self) -> &'static str {
+        match self {
+            MoedictDictionary::Mandarin => "a",
+            MoedictDictionary::Taiwanese => "t",
+            MoedictDictionary::Hakka => "h",
+            MoedictDictionary::CrossStrait => "c",
+        }
+    }
+}
+
+impl fmt::Display for MoedictDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MoedictDictionary::Mandarin => "mandarin",
+            MoedictDictionary::Taiwanese => "taiwanese",
+            MoedictDictionary::Hakka => "hakka",
+            MoedictDictionary::CrossStrait => "cross-strait",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for MoedictDictionary {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mandarin" | "a" => Ok(MoedictDictionary::Mandarin),
+            "taiwanese" | "t" => Ok(MoedictDictionary::Taiwanese),
+            "hakka" | "h" => Ok(MoedictDictionary::Hakka),
+            "cross-strait" | "c" => Ok(MoedictDictionary::CrossStrait),
+            _ => Err(anyhow!("Unsupport dictionary: {}", s)),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct MoedictRawResult {
-    #[serde(rename(deserialize = "t"))]
+    #[serde(rename(deserialize = "t"), alias = "title")]
     pub title: String,
     pub translation: Option<IndexMap<String, Vec<String>>>,
-    #[serde(rename(deserialize = "h"))]
+    #[serde(rename(deserialize = "h"), alias = "heteronyms")]
     pub heteronyms: Option<Vec<MoedictHeteronym>>,
     #[serde(rename(deserialize = "English"))]
     pub english: Option<String>,
+    #[serde(
+        rename(deserialize = "r"),
+        alias = "radical",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub radical: Option<String>,
+    #[serde(
+        rename(deserialize = "c"),
+        alias = "stroke_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stroke_count: Option<u32>,
+    /// Strokes outside the radical
+    #[serde(
+        rename(deserialize = "n"),
+        alias = "non_radical_stroke_count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub non_radical_stroke_count: Option<u32>,
+    #[serde(skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+}
+
+impl MoedictRawResult {
+    /// Linked words, synonyms and antonyms, in order of first appearance
+    pub fn references(&self) -> Vec<String> {
+        let mut references = Vec::new();
+        for heteronym in self.heteronyms.iter().flatten() {
+            let words = heteronym
+                .definitions
+                .iter()
+                .flatten()
+                .flat_map(|x| &x.references)
+                .chain(heteronym.synonyms.iter().flatten())
+                .chain(heteronym.antonyms.iter().flatten());
+            for word in words {
+                if !references.contains(word) {
+                    references.push(word.clone());
+                }
+            }
+        }
+
+        references
+    }
+
+    /// Rewrite the pinyin of every heteronym in `style`
+    pub fn romanize_pinyin(mut self, style: PinyinStyle) -> Self {
+        for heteronym in self.heteronyms.iter_mut().flatten() {
+            if let Some(pinyin) = &heteronym.pinyin {
+                heteronym.pinyin = Some(romanize(pinyin, None, style));
+            }
+        }
+
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MeowdictJyutPingResult {
     pub word: String,
+    pub jyutping: Vec<JyutPingReading>,
+    /// Set when the word is missing from words.hk and its reading was composed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<JyutPingSegment>>,
+}
+
+impl MeowdictJyutPingResult {
+    pub fn romanize(mut self, romanization: CantoneseRomanization) -> Self {
+        for reading in &mut self.jyutping {
+            reading.jyutping = convert_jyutping(&reading.jyutping, romanization);
+        }
+        for segment in self.segments.iter_mut().flatten() {
+            for jyutping in &mut segment.jyutping {
+                *jyutping = convert_jyutping(jyutping, romanization);
+            }
+        }
+
+        self
+    }
+}
+
+/// Part of a composed reading. `jyutping` is ranked and empty for unknown
+/// characters such as punctuation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JyutPingSegment {
+    pub text: String,
     pub jyutping: Vec<String>,
+    pub ambiguous: bool,
+}
+
+#[cfg(test)]
+impl JyutPingSegment {
+    pub fn new(text: &str, jyutping: &[&str]) -> Self {
+        JyutPingSegment {
+            text: text.to_string(),
+            jyutping: jyutping.iter().map(|x| x.to_string()).collect(),
+            ambiguous: jyutping.len() > 1,
+        }
+    }
+}
+
+/// One reading of a word, most frequent first. `count` and `share` are only
+/// known for single characters, which words.hk counts in its corpus.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JyutPingReading {
+    pub jyutping: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share: Option<f64>,
+    pub primary: bool,
+}
+
+#[cfg(test)]
+impl JyutPingReading {
+    pub fn new(jyutping: &str, count: Option<usize>, share: Option<f64>, primary: bool) -> Self {
+        JyutPingReading {
+            jyutping: jyutping.to_string(),
+            count,
+            share,
+            primary,
+        }
+    }
+}
+
+/// Cached readings, either ranked or a plain list from older caches
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredJyutPing {
+    Ranked(Vec<JyutPingReading>),
+    Plain(Vec<String>),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -59,7 +460,11 @@ pub struct MeowdictJsonResult {
     pub name: String,
     #[serde(flatten)]
     pub moedict_raw_result: Option<MoedictRawResult>,
-    pub jyutping: Option<Vec<String>>,
+    pub jyutping: Option<Vec<JyutPingReading>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jyutping_segments: Option<Vec<JyutPingSegment>>,
+    pub error: Option<String>,
+    pub jyutping_error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -70,232 +475,967 @@ pub struct WantWordsResult {
     pub word: String,
 }
 
-lazy_static! {
-    static ref CACHE_PATH_DIRECTORY: PathBuf =
-        dirs_next::cache_dir().unwrap_or_else(|| PathBuf::from("."));
-    static ref JYUTPING_CACHE_PATH: PathBuf = CACHE_PATH_DIRECTORY.join("jyutping.json");
-    static ref MOEDICT_INDEX_CACHE_PATH: PathBuf = CACHE_PATH_DIRECTORY.join("moedict_index.json");
+impl WantWordsResult {
+    pub fn is_zero_correlation(&self) -> bool {
+        self.correlation.trim_start_matches('0').is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MeowdictWantWordsResult {
+    pub description: String,
+    pub words: Vec<WantWordsResult>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
 }
 
 type JyutPingCharList = HashMap<String, HashMap<String, usize>>;
 type JyutPingWordList = HashMap<String, Vec<String>>;
-const MOEDICT_INDEX_URL: &str = "https://www.moedict.tw/a/index.json";
+const HAKKA_DIALECT_MARK: char = '\u{20DE}';
+const MOEDICT_WEB_URL: &str = "https://www.moedict.tw";
+const WORDSHK_CHARLIST_PATH: &str = "charlist.json";
+const WORDSHK_WORDLIST_PATH: &str = "wordslist.json";
+const JYUTPING_SEGMENT_MAX_LENGTH: usize = 8;
+const SEGMENT_MAX_LENGTH: usize = 8;
+const SUGGESTION_LIMIT: usize = 5;
 
-async fn request_moedict(keyword: &str, client: &Client) -> Result<MoedictRawResult> {
+async fn request_moedict(
+    keyword: &str,
+    dict: MoedictDictionary,
+    client: &MeowdictClient,
+) -> Result<Option<String>> {
     let response = client
-        .get(format!("https://www.moedict.tw/a/{}.json", keyword))
-        .send()
+        .get(
+            &client.backends.moedict,
+            &format!("{}/{}.json", dict.path(), keyword),
+            &[],
+        )
         .await?;
 
     match response.status().into() {
-        200 => Ok(serde_json::from_str(
-            response
-                .text()
-                .await?
-                .replace("`", "")
-                .replace("~", "")
-                .as_str(),
-        )?),
-        404 => Err(anyhow!("Could not find keyword: {}", keyword)),
+        200 => Ok(Some(response.text().await?)),
+        404 => Ok(None),
         _ => Err(anyhow!("Response status code: {}", response.status())),
     }
 }
 
-async fn request_moedict_index(client: &Client) -> Result<Vec<String>> {
-    Ok(client
-        .get(MOEDICT_INDEX_URL)
-        .send()
-        .await?
-        .json::<Vec<String>>()
-        .await?)
+/// Serve `key` from `cache`, calling `request` once the entry expired. `request`
+/// returns `None` when the backend has no such entry. Stale entries are only
+/// used when the backend cannot be reached (or in offline mode).
+async fn get_cached_entry<F, Fut>(
+    cache: &EntryCache,
+    key: &str,
+    client: &MeowdictClient,
+    request: F,
+) -> Result<Option<(String, bool)>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Option<String>>>,
+{
+    let stale = match cache.get(key) {
+        CacheEntry::Fresh(value) => return Ok(Some((value, false))),
+        CacheEntry::Stale(value) => Some(value),
+        CacheEntry::Missing => None,
+    };
+    if client.offline {
+        return stale
+            .map(|x| Some((x, true)))
+            .ok_or_else(|| anyhow!("Cannot find {} in cache (offline mode)", key));
+    }
+
+    match request().await {
+        Ok(Some(value)) => {
+            cache.put(key, &value).ok();
+            Ok(Some((value, false)))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => stale.map(|x| Some((x, true))).ok_or(e),
+    }
 }
 
-async fn get_wordshk(client: &Client) -> Result<HashMap<String, Vec<String>>> {
-    if !JYUTPING_CACHE_PATH.exists()
-        || (JYUTPING_CACHE_PATH.exists()
-            && (SystemTime::now()
-                .duration_since(fs::metadata(&*JYUTPING_CACHE_PATH)?.modified()?)?
-                .as_secs()
-                >= 24 * 60 * 60))
-    {
-        let (response_charlist, response_wordlist) = request_wordshk(client).await?;
-        create_dir_all(&*CACHE_PATH_DIRECTORY)?;
+pub fn parse_moedict_entry(s: &str) -> Result<MoedictRawResult> {
+    let mut result: MoedictRawResult = serde_json::from_str(s)?;
+    result.title = strip_link_markup(&result.title);
+    result.radical = result.radical.as_deref().map(strip_link_markup);
+    result.english = result.english.as_deref().map(strip_link_markup);
+    for translation in result.translation.iter_mut().flat_map(|x| x.values_mut()) {
+        for s in translation {
+            *s = strip_link_markup(s);
+        }
+    }
+    if let Some(heteronyms) = &mut result.heteronyms {
+        for heteronym in heteronyms {
+            for reading in [&mut heteronym.pinyin, &mut heteronym.bopomofo]
+                .into_iter()
+                .flatten()
+            {
+                *reading = strip_link_markup(reading);
+            }
+            for definition in heteronym.definitions.iter_mut().flatten() {
+                definition.parse_references();
+            }
+            heteronym.hakka = heteronym.pinyin.as_deref().and_then(parse_hakka_readings);
+            for reading in [&mut heteronym.pinyin, &mut heteronym.bopomofo]
+                .into_iter()
+                .flatten()
+            {
+                let (kind, rest) = ReadingKind::strip_label(reading);
+                if kind.is_some() {
+                    heteronym.reading_kind = kind;
+                    *reading = rest.to_string();
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
 
-        create_jyutping_cache(response_charlist, response_wordlist, &*JYUTPING_CACHE_PATH)
-    } else {
-        Ok(serde_json::from_reader(&File::open(
-            &*JYUTPING_CACHE_PATH,
-        )?)?)
+/// Hakka entries put every dialect into `p`, e.g. `四⃞ngâi 海⃞ngài`
+fn parse_hakka_readings(pinyin: &str) -> Option<Vec<HakkaReading>> {
+    if !pinyin.contains(HAKKA_DIALECT_MARK) {
+        return None;
     }
+
+    Some(
+        pinyin
+            .split_whitespace()
+            .filter_map(|x| x.split_once(HAKKA_DIALECT_MARK))
+            .map(|(dialect, romanization)| HakkaReading {
+                dialect: match dialect {
+                    "四" => "四縣",
+                    "海" => "海陸",
+                    "大" => "大埔",
+                    "平" => "饒平",
+                    "安" => "詔安",
+                    "南" => "南四縣",
+                    _ => dialect,
+                }
+                .to_string(),
+                romanization: romanization.to_string(),
+            })
+            .collect(),
+    )
 }
 
-fn create_jyutping_cache(
-    response_charlist: JyutPingCharList,
-    response_wordlist: JyutPingWordList,
-    cache_path: &Path,
-) -> Result<HashMap<String, Vec<String>>> {
-    let charlist: HashMap<String, Vec<String>> = response_charlist
+/// Error of a headword missing from moedict. `suggestions` is filled in by
+/// callers that look for close headwords.
+#[derive(Debug)]
+pub struct KeywordNotFound {
+    pub keyword: String,
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for KeywordNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not find keyword: {}", self.keyword)
+    }
+}
+
+impl std::error::Error for KeywordNotFound {}
+
+async fn get_moedict_entry(
+    keyword: &str,
+    dict: MoedictDictionary,
+    client: &MeowdictClient,
+) -> Result<MoedictRawResult> {
+    if let Some(entry) = get_offline_moedict(keyword, dict)? {
+        return parse_moedict_entry(&entry);
+    }
+    let cache = EntryCache::new(&format!("moedict_{}", dict.path()), client.cache.entry_ttl);
+    let (entry, stale) = get_cached_entry(&cache, keyword, client, || {
+        request_moedict(keyword, dict, client)
+    })
+    .await?
+    .ok_or_else(|| KeywordNotFound {
+        keyword: keyword.to_string(),
+        suggestions: Vec::new(),
+    })?;
+    let mut result = parse_moedict_entry(&entry)?;
+    result.stale = stale;
+
+    Ok(result)
+}
+
+/// Characters grouped by stroke count, `groups[n]` holding those with `n` strokes
+pub type StrokeGroups = Vec<Vec<String>>;
+
+#[derive(Deserialize)]
+struct MoedictCharacterStrokes {
+    #[serde(rename = "r")]
+    radical: Option<String>,
+    #[serde(rename = "c")]
+    stroke_count: Option<u32>,
+    #[serde(rename = "n")]
+    non_radical_stroke_count: Option<u32>,
+}
+
+fn group_by_strokes(items: impl Iterator<Item = (String, usize)>) -> StrokeGroups {
+    let mut groups: StrokeGroups = Vec::new();
+    for (word, strokes) in items {
+        if groups.len() <= strokes {
+            groups.resize(strokes + 1, Vec::new());
+        }
+        if !groups[strokes].contains(&word) {
+            groups[strokes].push(word);
+        }
+    }
+
+    groups
+}
+
+/// Stroke fields of the single characters in the offline store, if it exists
+fn offline_character_strokes(
+    dict: MoedictDictionary,
+) -> Result<Option<Vec<(String, MoedictCharacterStrokes)>>> {
+    let entries = match get_offline_moedict_characters(dict)? {
+        Some(entries) => entries,
+        None => return Ok(None),
+    };
+    let strokes = entries
         .into_iter()
-        .map(|(word, jyutping_map)| (word, jyutping_map.keys().map(|x| x.to_string()).collect()))
+        .filter_map(|(word, entry)| Some((word, serde_json::from_str(&entry).ok()?)))
         .collect();
-    let mut f = fs::File::create(cache_path)?;
-    let json: HashMap<String, Vec<String>> = charlist
+
+    Ok(Some(strokes))
+}
+
+/// Radicals of `strokes` by the stroke count of their own headword. Variant
+/// forms such as 氵 in 江 are written with fewer strokes, so radicals without
+/// a headword take the largest count left over by their characters.
+fn group_radicals(strokes: Vec<(String, MoedictCharacterStrokes)>) -> StrokeGroups {
+    let own_counts = strokes
+        .iter()
+        .filter_map(|(word, x)| Some((word.as_str(), x.stroke_count?)))
+        .collect::<HashMap<_, _>>();
+    let mut radicals: Vec<(String, u32)> = Vec::new();
+    for (_, x) in &strokes {
+        let radical = match &x.radical {
+            Some(radical) => strip_link_markup(radical),
+            None => continue,
+        };
+        let count = match own_counts.get(radical.as_str()) {
+            Some(count) => *count,
+            None => match x.stroke_count.zip(x.non_radical_stroke_count) {
+                Some((count, non_radical)) => count.saturating_sub(non_radical),
+                None => continue,
+            },
+        };
+        match radicals.iter_mut().find(|x| x.0 == radical) {
+            Some(item) => item.1 = item.1.max(count),
+            None => radicals.push((radical, count)),
+        }
+    }
+
+    group_by_strokes(radicals.into_iter().map(|(x, count)| (x, count as usize)))
+}
+
+fn group_radical_characters(
+    strokes: Vec<(String, MoedictCharacterStrokes)>,
+    radical: &str,
+) -> StrokeGroups {
+    group_by_strokes(strokes.into_iter().filter_map(|(word, x)| {
+        if strip_link_markup(x.radical.as_deref()?) != radical {
+            return None;
+        }
+        Some((word, x.non_radical_stroke_count? as usize))
+    }))
+}
+
+/// Radical indexes are `@.json` for all radicals and `@{radical}.json` for
+/// the characters of one
+async fn get_radical_index(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+    key: &str,
+) -> Result<StrokeGroups> {
+    let cache = EntryCache::new(&format!("moedict_{}", dict.path()), client.cache.entry_ttl);
+    let (entry, _) = get_cached_entry(&cache, key, client, || request_moedict(key, dict, client))
+        .await?
+        .ok_or_else(|| anyhow!("Could not find radical: {}", key.trim_start_matches('@')))?;
+    let groups = serde_json::from_str::<StrokeGroups>(&entry)?
         .into_iter()
-        .chain(response_wordlist.into_iter())
+        .map(|x| x.iter().map(|x| strip_link_markup(x)).collect())
         .collect();
-    f.write_all(serde_json::to_string(&json)?.as_bytes())?;
 
-    Ok(json)
+    Ok(groups)
+}
+
+pub async fn get_radicals(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+) -> Result<StrokeGroups> {
+    if let Some(strokes) = offline_character_strokes(dict)? {
+        return Ok(group_radicals(strokes));
+    }
+    let groups = get_radical_index(client, dict, "@").await?;
+
+    // Radicals have at least one stroke, so a filled first group holds 1 stroke
+    Ok(match groups.first() {
+        Some(x) if !x.is_empty() => std::iter::once(Vec::new()).chain(groups).collect(),
+        _ => groups,
+    })
+}
+
+/// Characters of `radical`, grouped by the strokes outside the radical
+pub async fn get_radical_characters(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+    radical: &str,
+) -> Result<StrokeGroups> {
+    if let Some(strokes) = offline_character_strokes(dict)? {
+        let groups = group_radical_characters(strokes, radical);
+        if groups.is_empty() {
+            return Err(anyhow!("Could not find radical: {}", radical));
+        }
+
+        return Ok(groups);
+    }
+
+    get_radical_index(client, dict, &format!("@{}", radical)).await
 }
 
-pub async fn get_moedict_index(client: &Client) -> Result<Vec<String>> {
-    if !MOEDICT_INDEX_CACHE_PATH.exists()
-        || (MOEDICT_INDEX_CACHE_PATH.exists()
-            && (SystemTime::now()
-                .duration_since(fs::metadata(&*MOEDICT_INDEX_CACHE_PATH)?.modified()?)?
+/// Response of a conditional request, `None` when the server answered 304
+type Revalidated<T> = Option<(T, CacheValidator)>;
+
+async fn request_revalidated<T: DeserializeOwned>(
+    client: &MeowdictClient,
+    mirrors: &[String],
+    path: &str,
+    validator: Option<&CacheValidator>,
+) -> Result<Revalidated<T>> {
+    let response = client
+        .get_conditional(
+            mirrors,
+            path,
+            validator.unwrap_or(&CacheValidator::default()),
+        )
+        .await?;
+    match response.status().into() {
+        200 => {
+            let validator = CacheValidator::from_headers(response.headers());
+            Ok(Some((response.json::<T>().await?, validator)))
+        }
+        304 if validator.is_some() => Ok(None),
+        _ => Err(anyhow!("Response status code: {}", response.status())),
+    }
+}
+
+async fn request_moedict_index(
+    dict: MoedictDictionary,
+    client: &MeowdictClient,
+    validator: Option<&CacheValidator>,
+) -> Result<Revalidated<Vec<String>>> {
+    request_revalidated(
+        client,
+        &client.backends.moedict,
+        &moedict_index_request_path(dict),
+        validator,
+    )
+    .await
+}
+
+fn moedict_index_request_path(dict: MoedictDictionary) -> String {
+    format!("{}/index.json", dict.path())
+}
+
+/// Whether a whole-file cache should be downloaded again. Offline mode keeps
+/// any existing cache, and an expired cache is still used if the download fails.
+fn should_refresh_cache(path: &Path, client: &MeowdictClient) -> Result<bool> {
+    Ok(!path.exists()
+        || (!client.offline
+            && SystemTime::now()
+                .duration_since(fs::metadata(path)?.modified()?)?
                 .as_secs()
-                >= 24 * 60 * 60))
-    {
-        let moedict_index = request_moedict_index(client).await?;
-        create_dir_all(&*CACHE_PATH_DIRECTORY)?;
+                >= client.cache.index_ttl))
+}
+
+/// Load a whole-file JSON cache, downloading it again under the cache lock when
+/// it is missing, expired or corrupt
+async fn get_file_cache<T, F, Fut>(
+    path: &Path,
+    client: &MeowdictClient,
+    read: fn(&Path) -> Option<T>,
+    download: F,
+) -> Result<T>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let read_fresh_cache = || -> Result<Option<T>> {
+        Ok(if should_refresh_cache(path, client)? {
+            None
+        } else {
+            read(path)
+        })
+    };
+    if let Some(value) = read_fresh_cache()? {
+        return Ok(value);
+    }
+    let _lock = CacheLock::acquire(path).await?;
+    // Another process may have refreshed the cache while we were waiting
+    if let Some(value) = read_fresh_cache()? {
+        return Ok(value);
+    }
+
+    match download().await {
+        Ok(value) => Ok(value),
+        Err(e) => read(path).ok_or(e),
+    }
+}
+
+async fn get_wordshk(client: &MeowdictClient) -> Result<SortedTable> {
+    if !JYUTPING_CACHE_PATH.exists() && LEGACY_JYUTPING_CACHE_PATH.exists() {
+        let _lock = CacheLock::acquire(&JYUTPING_CACHE_PATH).await?;
+        migrate_jyutping_cache(&LEGACY_JYUTPING_CACHE_PATH, &JYUTPING_CACHE_PATH)?;
+    }
+
+    get_file_cache(&JYUTPING_CACHE_PATH, client, open_jyutping_table, || {
+        download_wordshk(client)
+    })
+    .await
+}
+
+/// Sort readings by usage count, keeping the words.hk order for readings
+/// without a count, and work out the share of each counted reading
+fn rank_jyutping_readings(mut readings: Vec<(String, Option<usize>)>) -> Vec<JyutPingReading> {
+    readings.sort_by_key(|x| Reverse(x.1));
+    let total = readings.iter().filter_map(|x| x.1).sum::<usize>();
+
+    readings
+        .into_iter()
+        .enumerate()
+        .map(|(index, (jyutping, count))| JyutPingReading {
+            jyutping,
+            count,
+            share: count.filter(|_| total > 0).map(|x| x as f64 / total as f64),
+            primary: index == 0 && count.is_some(),
+        })
+        .collect()
+}
+
+fn parse_jyutping_readings(s: &str) -> Result<Vec<JyutPingReading>> {
+    Ok(match serde_json::from_str(s)? {
+        StoredJyutPing::Ranked(readings) => readings,
+        StoredJyutPing::Plain(jyutping) => {
+            rank_jyutping_readings(jyutping.into_iter().map(|x| (x, None)).collect())
+        }
+    })
+}
+
+fn open_jyutping_table(path: &Path) -> Option<SortedTable> {
+    SortedTable::open(path).ok()
+}
+
+/// Convert the JSON cache of older versions into the indexed table, keeping
+/// its age and HTTP validators
+fn migrate_jyutping_cache(legacy_path: &Path, cache_path: &Path) -> Result<()> {
+    if cache_path.exists() {
+        return Ok(());
+    }
+    let modified = fs::metadata(legacy_path).and_then(|x| x.modified());
+    if let Some(json) = read_json_cache::<HashMap<String, Vec<String>>>(legacy_path) {
+        let entries = json
+            .into_iter()
+            .map(|(word, jyutping)| {
+                let readings =
+                    rank_jyutping_readings(jyutping.into_iter().map(|x| (x, None)).collect());
+                Ok((word, serde_json::to_string(&readings)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        write_sorted_table(cache_path, entries)?;
+        if let Ok(modified) = modified {
+            File::options()
+                .write(true)
+                .open(cache_path)?
+                .set_modified(modified)?;
+        }
+        write_validators(cache_path, &read_validators(legacy_path))?;
+    }
+    remove_cache_file(legacy_path)?;
+
+    Ok(())
+}
+
+pub async fn refresh_wordshk(client: &MeowdictClient) -> Result<SortedTable> {
+    let _lock = CacheLock::acquire(&JYUTPING_CACHE_PATH).await?;
+
+    download_wordshk(client).await
+}
+
+async fn download_wordshk(client: &MeowdictClient) -> Result<SortedTable> {
+    let cache_path = &*JYUTPING_CACHE_PATH;
+    let validators = read_validators(cache_path);
+    let (charlist, wordlist) = request_wordshk(client, &validators).await?;
+    if charlist.is_none() && wordlist.is_none() {
+        if let Some(jyutping_table) = touch_cache(cache_path, open_jyutping_table) {
+            return Ok(jyutping_table);
+        }
+    }
+    // Both lists are merged into one file, so a list that did not change has
+    // to be downloaded again when the other one did
+    let (response_charlist, charlist_validator) = match charlist {
+        Some(charlist) => charlist,
+        None => request_wordshk_list(client, WORDSHK_CHARLIST_PATH).await?,
+    };
+    let (response_wordlist, wordlist_validator) = match wordlist {
+        Some(wordlist) => wordlist,
+        None => request_wordshk_list(client, WORDSHK_WORDLIST_PATH).await?,
+    };
+    let jyutping_table = create_jyutping_cache(response_charlist, response_wordlist, cache_path)?;
+    let validators = CacheValidators::from([
+        (WORDSHK_CHARLIST_PATH.to_string(), charlist_validator),
+        (WORDSHK_WORDLIST_PATH.to_string(), wordlist_validator),
+    ]);
+    write_validators(cache_path, &validators)?;
+
+    Ok(jyutping_table)
+}
+
+/// Merge both words.hk lists into a `SortedTable` of JSON encoded ranked
+/// readings, words from the wordlist replacing characters of the same key
+fn create_jyutping_cache(
+    response_charlist: JyutPingCharList,
+    response_wordlist: JyutPingWordList,
+    cache_path: &Path,
+) -> Result<SortedTable> {
+    let charlist = response_charlist.into_iter().map(|(word, jyutping_map)| {
+        // The charlist has no order of its own, so ties stay deterministic
+        let mut readings = jyutping_map
+            .into_iter()
+            .map(|(x, count)| (x, Some(count)))
+            .collect::<Vec<_>>();
+        readings.sort();
+        (word, rank_jyutping_readings(readings))
+    });
+    let wordlist = response_wordlist.into_iter().map(|(word, jyutping)| {
+        let readings = jyutping.into_iter().map(|x| (x, None));
+        (word, rank_jyutping_readings(readings.collect()))
+    });
+    let entries = charlist
+        .chain(wordlist)
+        .map(|(word, readings)| Ok((word, serde_json::to_string(&readings)?)))
+        .collect::<Result<Vec<_>>>()?;
+    write_sorted_table(cache_path, entries)?;
 
-        create_moedict_index_cache(moedict_index, &*MOEDICT_INDEX_CACHE_PATH)
-    } else {
-        Ok(serde_json::from_reader(&File::open(
-            &*MOEDICT_INDEX_CACHE_PATH,
-        )?)?)
+    SortedTable::open(cache_path)
+}
+
+pub async fn get_moedict_index(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+) -> Result<Vec<String>> {
+    if let Some(moedict_index) = get_offline_moedict_index(dict)? {
+        return Ok(moedict_index);
     }
+
+    get_file_cache(
+        &moedict_index_cache_path(dict),
+        client,
+        read_json_cache,
+        || download_moedict_index(client, dict),
+    )
+    .await
+}
+
+pub async fn refresh_moedict_index(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+) -> Result<Vec<String>> {
+    let _lock = CacheLock::acquire(&moedict_index_cache_path(dict)).await?;
+
+    download_moedict_index(client, dict).await
+}
+
+async fn download_moedict_index(
+    client: &MeowdictClient,
+    dict: MoedictDictionary,
+) -> Result<Vec<String>> {
+    let cache_path = moedict_index_cache_path(dict);
+    let request_path = moedict_index_request_path(dict);
+    let mut validators = read_validators(&cache_path);
+    let (moedict_index, validator) =
+        match request_moedict_index(dict, client, validators.get(&request_path)).await? {
+            Some(response) => response,
+            None => match touch_cache(&cache_path, read_json_cache) {
+                Some(moedict_index) => return Ok(moedict_index),
+                None => request_moedict_index(dict, client, None).await?.unwrap(),
+            },
+        };
+    let moedict_index = create_moedict_index_cache(moedict_index, &cache_path)?;
+    validators.insert(request_path, validator);
+    write_validators(&cache_path, &validators)?;
+
+    Ok(moedict_index)
 }
 
 fn create_moedict_index_cache(
     response_moedict_index: Vec<String>,
     cache_path: &Path,
 ) -> Result<Vec<String>> {
-    let mut f = fs::File::create(cache_path)?;
-    f.write_all(serde_json::to_string(&response_moedict_index)?.as_bytes())?;
+    write_atomic(
+        cache_path,
+        serde_json::to_string(&response_moedict_index)?.as_bytes(),
+    )?;
 
     Ok(response_moedict_index)
 }
 
-async fn request_wordshk(client: &Client) -> Result<(JyutPingCharList, JyutPingWordList)> {
+async fn request_wordshk(
+    client: &MeowdictClient,
+    validators: &CacheValidators,
+) -> Result<(Revalidated<JyutPingCharList>, Revalidated<JyutPingWordList>)> {
     tokio::try_join! {
-        async {
-            Ok::<_, Error>(client
-                .get("https://words.hk/faiman/analysis/charlist.json")
-                .send()
-                .await?
-                .json::<JyutPingCharList>()
-                .await?)
-        },
-        async {
-            Ok(client
-                .get("https://words.hk/faiman/analysis/wordslist.json")
-                .send()
-                .await?
-                .json::<JyutPingWordList>()
-                .await?)
-        },
-    }
-}
-
-pub async fn get_dict_result(client: &Client, words: &[String]) -> Result<Vec<MoedictRawResult>> {
+        request_revalidated(
+            client,
+            &client.backends.wordshk,
+            WORDSHK_CHARLIST_PATH,
+            validators.get(WORDSHK_CHARLIST_PATH),
+        ),
+        request_revalidated(
+            client,
+            &client.backends.wordshk,
+            WORDSHK_WORDLIST_PATH,
+            validators.get(WORDSHK_WORDLIST_PATH),
+        ),
+    }
+}
+
+/// Unconditional download of one words.hk list
+async fn request_wordshk_list<T: DeserializeOwned>(
+    client: &MeowdictClient,
+    path: &str,
+) -> Result<(T, CacheValidator)> {
+    request_revalidated(client, &client.backends.wordshk, path, None)
+        .await?
+        .ok_or_else(|| anyhow!("Unexpected 304 response for {}", path))
+}
+
+pub async fn get_dict_result(
+    client: &MeowdictClient,
+    words: &[String],
+    dict: MoedictDictionary,
+) -> Vec<Result<MoedictRawResult>> {
     let mut tesk = Vec::new();
     for word in words {
-        tesk.push(request_moedict(word, client));
+        tesk.push(get_moedict_entry(word, dict, client));
     }
 
-    Ok(future::try_join_all(tesk).await?)
+    future::join_all(tesk).await
 }
 
 pub async fn get_jyutping_result(
-    client: &Client,
+    client: &MeowdictClient,
     words: &[String],
-) -> Result<Vec<MeowdictJyutPingResult>> {
-    let mut result = Vec::new();
-    let jyutping_map = get_wordshk(client).await?;
-    for word in words {
-        result.push(MeowdictJyutPingResult {
+) -> Result<Vec<Result<MeowdictJyutPingResult>>> {
+    let jyutping_table = get_wordshk(client).await?;
+
+    Ok(words
+        .iter()
+        .map(|word| lookup_jyutping(&jyutping_table, word))
+        .collect())
+}
+
+fn lookup_jyutping(jyutping_table: &SortedTable, word: &str) -> Result<MeowdictJyutPingResult> {
+    if let Some(jyutping) = jyutping_table.get(word)? {
+        return Ok(MeowdictJyutPingResult {
             word: word.to_owned(),
-            jyutping: jyutping_map
-                .get(word)
-                .ok_or_else(|| anyhow!("Cannot find jyutping: {}", word))?
-                .to_owned(),
+            jyutping: parse_jyutping_readings(&jyutping)?,
+            segments: None,
         });
     }
+    let segments = segment_jyutping(jyutping_table, word)?;
+    if segments.iter().all(|x| x.jyutping.is_empty()) {
+        return Err(anyhow!("Cannot find jyutping: {}", word));
+    }
+    let composed = segments
+        .iter()
+        .map(|x| x.jyutping.first().unwrap_or(&x.text).as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
 
-    Ok(result)
+    Ok(MeowdictJyutPingResult {
+        word: word.to_owned(),
+        jyutping: vec![JyutPingReading {
+            jyutping: composed,
+            count: None,
+            share: None,
+            primary: false,
+        }],
+        segments: Some(segments),
+    })
 }
 
-pub async fn set_json_result(client: &Client, words: &[String]) -> Vec<MeowdictJsonResult> {
-    let mut result = Vec::new();
-    let moedict_raw_results = get_dict_result(client, words).await.ok();
-    let jyutping = get_jyutping_result(client, words).await.ok();
-
-    match moedict_raw_results {
-        Some(moedict_raw_results) => match jyutping {
-            Some(jyutping) => {
-                let zip = moedict_raw_results.into_iter().zip(jyutping.into_iter());
-                for (index, (moedict_raw_result, jyutping)) in zip.into_iter().enumerate() {
-                    result.push(MeowdictJsonResult {
-                        name: words[index].to_owned(),
-                        moedict_raw_result: Some(moedict_raw_result),
-                        jyutping: Some(jyutping.jyutping),
-                    });
-                }
-            }
-            None => {
-                for (index, moedict_raw_result) in moedict_raw_results.into_iter().enumerate() {
-                    result.push(MeowdictJsonResult {
-                        name: words[index].to_owned(),
-                        moedict_raw_result: Some(moedict_raw_result),
-                        jyutping: None,
-                    });
-                }
+/// Split `word` into the longest known words from the left, falling back to
+/// single characters
+fn segment_jyutping(jyutping_table: &SortedTable, word: &str) -> Result<Vec<JyutPingSegment>> {
+    let chars = word.chars().collect::<Vec<_>>();
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut segment = None;
+        for end in (start + 1..=chars.len().min(start + JYUTPING_SEGMENT_MAX_LENGTH)).rev() {
+            let text = chars[start..end].iter().collect::<String>();
+            if let Some(jyutping) = jyutping_table.get(&text)? {
+                segment = Some((end, text, parse_jyutping_readings(&jyutping)?));
+                break;
             }
-        },
-        None => match jyutping {
-            Some(jyutping) => {
-                for (index, jyutping_item) in jyutping.into_iter().enumerate() {
-                    result.push(MeowdictJsonResult {
-                        name: words[index].to_owned(),
-                        moedict_raw_result: None,
-                        jyutping: Some(jyutping_item.jyutping),
-                    })
-                }
+        }
+        let (end, text, readings) =
+            segment.unwrap_or_else(|| (start + 1, chars[start].to_string(), Vec::new()));
+        segments.push(JyutPingSegment {
+            text,
+            ambiguous: readings.len() > 1,
+            jyutping: readings.into_iter().map(|x| x.jyutping).collect(),
+        });
+        start = end;
+    }
+
+    Ok(segments)
+}
+
+/// Headwords of `index` close to `word`: its `variants` first, then words one
+/// character away, then words sharing the most characters with it
+pub fn rank_suggestions(word: &str, variants: &[String], index: &[String]) -> Vec<String> {
+    let chars = word.chars().collect::<Vec<_>>();
+    let mut candidates = index
+        .iter()
+        .filter(|x| x.as_str() != word)
+        .filter_map(|candidate| {
+            let candidate_chars = candidate.chars().collect::<Vec<_>>();
+            let rank = if variants.contains(candidate) {
+                2
+            } else if is_one_char_away(&chars, &candidate_chars) {
+                1
+            } else {
+                0
+            };
+            let shared = chars
+                .iter()
+                .filter(|x| candidate_chars.contains(x))
+                .collect::<HashSet<_>>();
+            if rank == 0 && shared.is_empty() {
+                return None;
             }
-            None => {
-                for i in words {
-                    result.push(MeowdictJsonResult {
-                        name: i.to_owned(),
-                        moedict_raw_result: None,
-                        jyutping: None,
-                    });
+
+            Some((
+                rank,
+                shared.len(),
+                chars.len().abs_diff(candidate_chars.len()),
+                candidate,
+            ))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+    candidates
+        .into_iter()
+        .take(SUGGESTION_LIMIT)
+        .map(|x| x.3.clone())
+        .collect()
+}
+
+/// One character replaced, inserted or removed
+fn is_one_char_away(a: &[char], b: &[char]) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    match long.len() - short.len() {
+        0 => a.iter().zip(b).filter(|(x, y)| x != y).count() == 1,
+        1 => {
+            let prefix = short.iter().zip(long).take_while(|(x, y)| x == y).count();
+            short[prefix..] == long[prefix + 1..]
+        }
+        _ => false,
+    }
+}
+
+/// Segments worth looking up: Chinese words, not punctuation or ASCII runs
+pub fn is_chinese_word(s: &str) -> bool {
+    !s.is_ascii() && s.chars().all(char::is_alphanumeric)
+}
+
+/// Split `text` by forward maximum matching, taking the longest prefix that
+/// `is_word` knows. Unknown characters become segments of their own, while
+/// runs of ASCII letters and digits stay together.
+pub fn segment_text<F: Fn(&str) -> bool>(text: &str, is_word: F) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        if chars[start].is_whitespace() {
+            start += 1;
+            continue;
+        }
+        let end = if chars[start].is_ascii_alphanumeric() {
+            start
+                + chars[start..]
+                    .iter()
+                    .take_while(|x| x.is_ascii_alphanumeric())
+                    .count()
+        } else {
+            (start + 2..=chars.len().min(start + SEGMENT_MAX_LENGTH))
+                .rev()
+                .find(|end| is_word(&chars[start..*end].iter().collect::<String>()))
+                .unwrap_or(start + 1)
+        };
+        segments.push(chars[start..end].iter().collect());
+        start = end;
+    }
+
+    segments
+}
+
+/// Headwords of the moedict index of a dictionary and the words.hk wordlist,
+/// which is skipped when it cannot be loaded
+pub struct WordList {
+    moedict_index: HashSet<String>,
+    jyutping_table: Option<SortedTable>,
+}
+
+impl WordList {
+    pub async fn new(client: &MeowdictClient, dict: MoedictDictionary) -> Result<Self> {
+        Ok(WordList {
+            moedict_index: get_moedict_index(client, dict).await?.into_iter().collect(),
+            jyutping_table: get_wordshk(client).await.ok(),
+        })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.moedict_index.contains(word)
+            || self
+                .jyutping_table
+                .as_ref()
+                .is_some_and(|x| matches!(x.get(word), Ok(Some(_))))
+    }
+
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        segment_text(text, |word| self.contains(word))
+    }
+}
+
+pub async fn get_segments(
+    client: &MeowdictClient,
+    text: &str,
+    dict: MoedictDictionary,
+) -> Result<Vec<String>> {
+    Ok(WordList::new(client, dict).await?.segment(text))
+}
+
+pub async fn set_json_result(
+    client: &MeowdictClient,
+    words: &[String],
+    dict: MoedictDictionary,
+    romanization: CantoneseRomanization,
+) -> Vec<MeowdictJsonResult> {
+    let (moedict_raw_results, jyutping_results) = tokio::join!(
+        get_dict_result(client, words, dict),
+        get_jyutping_result(client, words)
+    );
+
+    merge_json_results(words, moedict_raw_results, jyutping_results, romanization)
+}
+
+/// Pair up both lookups of every word. A failed words.hk download is
+/// reported as the jyutping error of every word.
+fn merge_json_results(
+    words: &[String],
+    moedict_raw_results: Vec<Result<MoedictRawResult>>,
+    jyutping_results: Result<Vec<Result<MeowdictJyutPingResult>>>,
+    romanization: CantoneseRomanization,
+) -> Vec<MeowdictJsonResult> {
+    let jyutping_results = match jyutping_results {
+        Ok(results) => results
+            .into_iter()
+            .map(|x| x.map_err(|e| e.to_string()))
+            .collect(),
+        Err(e) => vec![Err(e.to_string()); words.len()],
+    };
+
+    words
+        .iter()
+        .zip(moedict_raw_results)
+        .zip(jyutping_results)
+        .map(|((word, moedict_raw_result), jyutping_result)| {
+            let (jyutping, jyutping_segments, jyutping_error) = match jyutping_result {
+                Ok(result) => {
+                    let result = result.romanize(romanization);
+                    (Some(result.jyutping), result.segments, None)
                 }
+                Err(e) => (None, None, Some(e)),
+            };
+            let (moedict_raw_result, error) = match moedict_raw_result {
+                Ok(moedict_raw_result) => (Some(moedict_raw_result), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            MeowdictJsonResult {
+                name: word.to_owned(),
+                moedict_raw_result,
+                jyutping,
+                jyutping_segments,
+                error,
+                jyutping_error,
             }
-        },
-    };
+        })
+        .collect()
+}
+
+async fn request_wantwords(keyword: &str, client: &MeowdictClient) -> Result<Option<String>> {
+    let response = client
+        .get(
+            &client.backends.wantwords,
+            "",
+            &[("description", keyword), ("mode", "CC")],
+        )
+        .await?;
 
-    result
+    match response.status().into() {
+        200 => Ok(Some(response.text().await?)),
+        _ => Err(anyhow!("Response status code: {}", response.status())),
+    }
 }
 
-async fn request_wantwords(keyword: &str, client: &Client) -> Result<Vec<WantWordsResult>> {
-    Ok(client
-        .get(format!(
-            "https://wantwords.thunlp.org/ChineseRD/?description={}&mode=CC",
-            keyword
-        ))
-        .send()
-        .await?
-        .json::<Vec<WantWordsResult>>()
-        .await?)
+async fn get_wantwords_entry(
+    keyword: &str,
+    client: &MeowdictClient,
+) -> Result<(Vec<WantWordsResult>, bool)> {
+    let cache = EntryCache::new("wantwords", client.cache.entry_ttl);
+    let (entry, stale) = get_cached_entry(&cache, keyword, client, || {
+        request_wantwords(keyword, client)
+    })
+    .await?
+    .ok_or_else(|| anyhow!("Could not find words: {}", keyword))?;
+
+    Ok((serde_json::from_str(&entry)?, stale))
 }
 
-pub async fn get_wantwords(words: &[String], client: &Client) -> Result<Vec<Vec<WantWordsResult>>> {
+pub async fn get_wantwords(
+    words: &[String],
+    client: &MeowdictClient,
+) -> Vec<MeowdictWantWordsResult> {
     let mut tesk = Vec::new();
     for word in words {
-        tesk.push(request_wantwords(word, client));
+        tesk.push(get_wantwords_entry(word, client));
     }
-    Ok(future::try_join_all(tesk).await?)
+
+    words
+        .iter()
+        .zip(future::join_all(tesk).await)
+        .map(|(word, result)| match result {
+            Ok((wantwords_result, stale)) => MeowdictWantWordsResult {
+                description: word.to_owned(),
+                words: wantwords_result,
+                error: None,
+                stale,
+            },
+            Err(e) => MeowdictWantWordsResult {
+                description: word.to_owned(),
+                words: Vec::new(),
+                error: Some(e.to_string()),
+                stale: false,
+            },
+        })
+        .collect()
 }
 
 #[test]
@@ -305,32 +1445,165 @@ fn test_cache_jyutping_result() {
     let mut charlist_value = HashMap::new();
     charlist_value.insert("ngo5".to_string(), 0usize);
     response_charlist.insert("我".to_string(), charlist_value);
+    response_charlist.insert(
+        "行".to_string(),
+        HashMap::from([("hang4".to_string(), 25), ("haang4".to_string(), 75)]),
+    );
     let mut response_wordlist: JyutPingWordList = HashMap::new();
     response_wordlist.insert("我哋".to_string(), vec!["ngo5 dei6".to_string()]);
     let file = NamedTempFile::new().unwrap();
-    let json = create_jyutping_cache(response_charlist, response_wordlist, file.path()).unwrap();
+    let table = create_jyutping_cache(response_charlist, response_wordlist, file.path()).unwrap();
+    let get = |word| parse_jyutping_readings(&table.get(word).unwrap().unwrap()).unwrap();
+    assert_eq!(
+        get("我"),
+        vec![JyutPingReading::new("ngo5", Some(0), None, true)]
+    );
+    assert_eq!(
+        get("行"),
+        vec![
+            JyutPingReading::new("haang4", Some(75), Some(0.75), true),
+            JyutPingReading::new("hang4", Some(25), Some(0.25), false),
+        ]
+    );
+    assert_eq!(
+        get("我哋"),
+        vec![JyutPingReading::new("ngo5 dei6", None, None, false)]
+    );
+    assert_eq!(
+        parse_jyutping_readings(r#"["ngo5"]"#).unwrap(),
+        vec![JyutPingReading::new("ngo5", None, None, false)]
+    );
+}
+
+#[test]
+fn test_rank_jyutping_readings() {
+    assert_eq!(
+        rank_jyutping_readings(vec![
+            ("hang4".to_string(), None),
+            ("haang4".to_string(), None),
+            ("hong4".to_string(), None),
+        ]),
+        vec![
+            JyutPingReading::new("hang4", None, None, false),
+            JyutPingReading::new("haang4", None, None, false),
+            JyutPingReading::new("hong4", None, None, false),
+        ]
+    );
+    assert_eq!(
+        rank_jyutping_readings(vec![
+            ("hang4".to_string(), Some(10)),
+            ("hong4".to_string(), Some(0)),
+            ("haang4".to_string(), Some(30)),
+        ]),
+        vec![
+            JyutPingReading::new("haang4", Some(30), Some(0.75), true),
+            JyutPingReading::new("hang4", Some(10), Some(0.25), false),
+            JyutPingReading::new("hong4", Some(0), Some(0.0), false),
+        ]
+    );
+}
 
-    assert_eq!(json["我"], vec!["ngo5".to_string()]);
-    assert_eq!(json["我哋"], vec!["ngo5 dei6".to_string()]);
+#[test]
+fn test_compose_jyutping() {
+    use tempfile::NamedTempFile;
+    let response_charlist: JyutPingCharList = HashMap::from([
+        ("去".to_string(), HashMap::from([("heoi3".to_string(), 10)])),
+        (
+            "行".to_string(),
+            HashMap::from([("hang4".to_string(), 25), ("haang4".to_string(), 75)]),
+        ),
+    ]);
+    let response_wordlist: JyutPingWordList = HashMap::from([
+        ("我哋".to_string(), vec!["ngo5 dei6".to_string()]),
+        ("行街".to_string(), vec!["haang4 gaai1".to_string()]),
+    ]);
+    let file = NamedTempFile::new().unwrap();
+    let table = create_jyutping_cache(response_charlist, response_wordlist, file.path()).unwrap();
+    let result = lookup_jyutping(&table, "我哋去行街，行").unwrap();
+    assert_eq!(
+        result.jyutping[0].jyutping,
+        "ngo5 dei6 heoi3 haang4 gaai1 ， haang4"
+    );
+    assert_eq!(
+        result.segments.unwrap(),
+        vec![
+            JyutPingSegment::new("我哋", &["ngo5 dei6"]),
+            JyutPingSegment::new("去", &["heoi3"]),
+            JyutPingSegment::new("行街", &["haang4 gaai1"]),
+            JyutPingSegment::new("，", &[]),
+            JyutPingSegment::new("行", &["haang4", "hang4"]),
+        ]
+    );
+    assert!(lookup_jyutping(&table, "行").unwrap().segments.is_none());
+    assert!(lookup_jyutping(&table, "，。").is_err());
+}
+
+#[test]
+fn test_migrate_jyutping_cache() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let legacy_path = dir.path().join("jyutping.json");
+    let cache_path = dir.path().join("jyutping.db");
+    fs::write(&legacy_path, r#"{"我":["ngo5"],"我哋":["ngo5 dei6"]}"#).unwrap();
+    File::options()
+        .write(true)
+        .open(&legacy_path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    migrate_jyutping_cache(&legacy_path, &cache_path).unwrap();
+    let table = SortedTable::open(&cache_path).unwrap();
+
+    assert!(!legacy_path.exists());
+    assert_eq!(table.keys().unwrap(), vec!["我", "我哋"]);
+    assert_eq!(
+        table.get("我").unwrap().as_deref(),
+        Some(r#"[{"jyutping":"ngo5","primary":false}]"#)
+    );
+    assert_eq!(
+        fs::metadata(&cache_path).unwrap().modified().unwrap(),
+        SystemTime::UNIX_EPOCH
+    );
 }
 
 #[tokio::test]
 async fn test_moedict_api_result() {
-    let client = reqwest::Client::new();
+    use crate::{cache::MeowdictCacheConfig, client::MeowdictBackends};
+    let client = MeowdictClient::new(
+        MeowdictBackends::default(),
+        MeowdictCacheConfig::default(),
+        false,
+    );
     let keyword = "我";
-    let result = request_moedict(keyword, &client).await.unwrap();
+    let response = request_moedict(keyword, MoedictDictionary::Mandarin, &client)
+        .await
+        .unwrap()
+        .unwrap();
+    let result = parse_moedict_entry(&response).unwrap();
     let result_str = serde_json::to_string(&result).unwrap();
-    let right_result = r#"{"title":"我","translation":{"Deutsch":["ich (mir, mich) <Personalpronomen 1. Pers.&gt (Pron)"],"English":["I","me","my"],"francais":["je","moi"]},"heteronyms":[{"pinyin":"（語音）wǒ","bopomofo":"（語音）ㄨㄛˇ","definitions":[{"word_type":"代","quote":["《易經．中孚卦．九二》：「我有好爵，吾與爾靡之。」","《詩經．小雅．采薇》：「昔我往矣，楊柳依依；今我來思，雨雪霏霏。」"],"example":null,"def":"自稱。","link":null},{"word_type":"代","quote":["《左傳．莊公十年》：「春，齊師伐我。」","《漢書．卷五四．李廣傳》：「我軍雖煩擾，虜亦不得犯我。」"],"example":null,"def":"自稱己方。","link":null},{"word_type":"形","quote":["《論語．述而》：「述而不作，信而好古，竊比於我老彭。」","漢．曹操〈步出夏門行〉：「經過至我碣石，心惆悵我東海。」"],"example":null,"def":"表示親切之意的語詞。","link":null},{"word_type":"名","quote":["《論語．子罕》：「毋意，毋必，毋固，毋我。」"],"example":["如：「大公無我」。"],"def":"私心、私意。","link":null},{"word_type":"名","quote":null,"example":null,"def":"姓。如戰國時有我子。","link":null}]},{"pinyin":"（讀音）ě","bopomofo":"（讀音）ㄜˇ","definitions":[{"word_type":null,"quote":null,"example":null,"def":"(一)之讀音。","link":null}]}],"english":"I"}"#;
+    let right_result = r#"{"title":"我","translation":{"Deutsch":["ich (mir, mich) <Personalpronomen 1. Pers.&gt (Pron)"],"English":["I","me","my"],"francais":["je","moi"]},"heteronyms":[{"pinyin":"wǒ","bopomofo":"ㄨㄛˇ","reading_kind":"colloquial","definitions":[{"word_type":"代","quote":["《易經．中孚卦．九二》：「我有好爵，吾與爾靡之。」","《詩經．小雅．采薇》：「昔我往矣，楊柳依依；今我來思，雨雪霏霏。」"],"example":null,"def":"自稱。","link":null},{"word_type":"代","quote":["《左傳．莊公十年》：「春，齊師伐我。」","《漢書．卷五四．李廣傳》：「我軍雖煩擾，虜亦不得犯我。」"],"example":null,"def":"自稱己方。","link":null},{"word_type":"形","quote":["《論語．述而》：「述而不作，信而好古，竊比於我老彭。」","漢．曹操〈步出夏門行〉：「經過至我碣石，心惆悵我東海。」"],"example":null,"def":"表示親切之意的語詞。","link":null},{"word_type":"名","quote":["《論語．子罕》：「毋意，毋必，毋固，毋我。」"],"example":["如：「大公無我」。"],"def":"私心、私意。","link":null},{"word_type":"名","quote":null,"example":null,"def":"姓。如戰國時有我子。","link":null}]},{"pinyin":"ě","bopomofo":"ㄜˇ","reading_kind":"literary","definitions":[{"word_type":null,"quote":null,"example":null,"def":"(一)之讀音。","link":null}]}],"english":"I"}"#;
 
     assert_eq!(result_str, right_result);
 }
 
 #[tokio::test]
 async fn test_wordshk_api_result() {
-    let client = reqwest::Client::new();
+    use crate::{cache::MeowdictCacheConfig, client::MeowdictBackends};
+    let client = MeowdictClient::new(
+        MeowdictBackends::default(),
+        MeowdictCacheConfig::default(),
+        false,
+    );
     let word_1 = "我";
     let word_2 = "我哋";
-    let (wordshk_charlist, wordshk_wordlist) = request_wordshk(&client).await.unwrap();
+    let (wordshk_charlist, wordshk_wordlist) =
+        match request_wordshk(&client, &CacheValidators::new())
+            .await
+            .unwrap()
+        {
+            (Some((charlist, _)), Some((wordlist, _))) => (charlist, wordlist),
+            _ => unreachable!(),
+        };
     let mut result_1 = HashMap::new();
     result_1.insert("ngo5".to_string(), 41usize);
     let result_2 = vec!["ngo5 dei6".to_string()];
@@ -338,3 +1611,255 @@ async fn test_wordshk_api_result() {
     assert_eq!(wordshk_charlist[word_1], result_1);
     assert_eq!(wordshk_wordlist[word_2], result_2);
 }
+
+#[test]
+fn test_merge_json_results() {
+    let words = vec!["我".to_string(), "貓".to_string()];
+    let moedict_raw_results = || {
+        vec![
+            parse_moedict_entry(r#"{"t":"我"}"#),
+            Err(anyhow!("Could not find keyword: 貓")),
+        ]
+    };
+    let jyutping_results = Ok(vec![
+        Ok(MeowdictJyutPingResult {
+            word: "我".to_string(),
+            jyutping: rank_jyutping_readings(vec![("ngo5".to_string(), Some(41))]),
+            segments: None,
+        }),
+        Err(anyhow!("Could not find jyutping: 貓")),
+    ]);
+    let results = merge_json_results(
+        &words,
+        moedict_raw_results(),
+        jyutping_results,
+        CantoneseRomanization::Jyutping,
+    );
+
+    assert!(results[0].jyutping.is_some());
+    assert_eq!(results[0].error, None);
+    assert_eq!(results[0].jyutping_error, None);
+    assert!(results[1].jyutping.is_none());
+    assert_eq!(
+        results[1].error.as_deref(),
+        Some("Could not find keyword: 貓")
+    );
+    assert_eq!(
+        results[1].jyutping_error.as_deref(),
+        Some("Could not find jyutping: 貓")
+    );
+
+    let results = merge_json_results(
+        &words,
+        moedict_raw_results(),
+        Err(anyhow!("Response status code: 503")),
+        CantoneseRomanization::Jyutping,
+    );
+
+    assert!(results[0].moedict_raw_result.is_some());
+    assert!(results
+        .iter()
+        .all(|x| x.jyutping_error.as_deref() == Some("Response status code: 503")));
+}
+
+#[test]
+fn test_parse_dialect_entry() {
+    let hakka = parse_moedict_entry(
+        r#"{"t":"我","h":[{"p":"四⃞ngâi 海⃞ngài","d":[{"f":"自稱。","type":"代"}]}]}"#,
+    )
+    .unwrap();
+    let taiwanese =
+        parse_moedict_entry(r#"{"t":"我","h":[{"T":"guá","reading":"文","d":[{"f":"自稱。"}]}]}"#)
+            .unwrap();
+    let hakka_heteronym = &hakka.heteronyms.unwrap()[0];
+    let taiwanese_heteronym = &taiwanese.heteronyms.unwrap()[0];
+
+    assert_eq!(
+        hakka_heteronym.hakka,
+        Some(vec![
+            HakkaReading {
+                dialect: "四縣".to_string(),
+                romanization: "ngâi".to_string(),
+            },
+            HakkaReading {
+                dialect: "海陸".to_string(),
+                romanization: "ngài".to_string(),
+            },
+        ])
+    );
+    assert_eq!(taiwanese_heteronym.tailo.as_deref(), Some("guá"));
+    assert_eq!(taiwanese_heteronym.reading.as_deref(), Some("文"));
+    assert_eq!(taiwanese_heteronym.hakka, None);
+}
+
+#[test]
+fn test_parse_link_markup() {
+    let result = parse_moedict_entry(
+        r#"{"t":"`我~","h":[{"p":"wǒ","d":[{"f":"`自~`稱~。","q":["「`我~`有~好爵」"],"l":["見「`吾~」條。"]}]}]}"#,
+    )
+    .unwrap();
+    let definition = &result.heteronyms.as_ref().unwrap()[0]
+        .definitions
+        .as_ref()
+        .unwrap()[0];
+    let json = serde_json::to_value(&result).unwrap();
+
+    assert_eq!(result.title, "我");
+    assert_eq!(definition.def.as_deref(), Some("`自~`稱~。"));
+    assert_eq!(definition.references, vec!["自", "稱", "我", "有", "吾"]);
+    assert_eq!(result.references(), vec!["自", "稱", "我", "有", "吾"]);
+    assert_eq!(json["heteronyms"][0]["definitions"][0]["def"], "自稱。");
+    assert_eq!(
+        json["heteronyms"][0]["definitions"][0]["quote"][0],
+        "「我有好爵」"
+    );
+    assert_eq!(
+        json["heteronyms"][0]["definitions"][0]["link"][0],
+        "見「吾」條。"
+    );
+    assert_eq!(
+        json["heteronyms"][0]["definitions"][0]["references"],
+        serde_json::json!(["自", "稱", "我", "有", "吾"])
+    );
+    assert_eq!(
+        MoedictDictionary::Taiwanese.web_url("我"),
+        "https://www.moedict.tw/'%E6%88%91"
+    );
+}
+
+#[test]
+fn test_parse_character_fields() {
+    let result = parse_moedict_entry(
+        r#"{"t":"我","r":"`戈~","c":7,"n":3,"h":[{"p":"wǒ","d":[{"f":"`自~`稱~。","s":"`自己~,`本人~","a":"`你~"}],"a":["他"]}]}"#,
+    )
+    .unwrap();
+    let heteronym = &result.heteronyms.as_ref().unwrap()[0];
+    let definition = &heteronym.definitions.as_ref().unwrap()[0];
+    let json = serde_json::to_value(&result).unwrap();
+
+    assert_eq!(result.radical.as_deref(), Some("戈"));
+    assert_eq!(result.stroke_count, Some(7));
+    assert_eq!(result.non_radical_stroke_count, Some(3));
+    assert_eq!(
+        definition.synonyms,
+        Some(vec!["自己".to_string(), "本人".to_string()])
+    );
+    assert_eq!(definition.antonyms, Some(vec!["你".to_string()]));
+    assert_eq!(heteronym.antonyms, Some(vec!["他".to_string()]));
+    assert_eq!(
+        result.references(),
+        vec!["自", "稱", "自己", "本人", "你", "他"]
+    );
+    assert_eq!(json["radical"], "戈");
+    assert_eq!(json["stroke_count"], 7);
+    assert_eq!(json["non_radical_stroke_count"], 3);
+    assert_eq!(
+        json["heteronyms"][0]["definitions"][0]["synonyms"],
+        serde_json::json!(["自己", "本人"])
+    );
+    assert_eq!(
+        serde_json::from_value::<MoedictRawResult>(json)
+            .unwrap()
+            .stroke_count,
+        Some(7)
+    );
+}
+
+#[test]
+fn test_parse_reading_kind() {
+    let result = parse_moedict_entry(
+        r#"{"t":"我","h":[{"p":"（語音）wǒ","b":"（語音）ㄨㄛˇ"},{"p":"（讀音）ě","b":"（讀音）ㄜˇ"},{"p":"wǒ"}]}"#,
+    )
+    .unwrap();
+    let heteronyms = result.heteronyms.unwrap();
+
+    assert_eq!(heteronyms[0].reading_kind, Some(ReadingKind::Colloquial));
+    assert_eq!(heteronyms[0].pinyin.as_deref(), Some("wǒ"));
+    assert_eq!(heteronyms[0].bopomofo.as_deref(), Some("ㄨㄛˇ"));
+    assert_eq!(heteronyms[1].reading_kind, Some(ReadingKind::Literary));
+    assert_eq!(heteronyms[1].pinyin.as_deref(), Some("ě"));
+    assert_eq!(heteronyms[2].reading_kind, None);
+}
+
+#[test]
+fn test_group_radicals() {
+    let strokes = || {
+        [
+            ("我", r#"{"t":"我","r":"`戈~","c":7,"n":3}"#),
+            ("戈", r#"{"t":"戈","r":"`戈~","c":4,"n":0}"#),
+            ("戊", r#"{"t":"戊","r":"`戈~","c":5,"n":1}"#),
+            ("口", r#"{"t":"口","r":"`口~","c":3,"n":0}"#),
+            ("江", r#"{"t":"江","r":"`水~","c":6,"n":3}"#),
+            ("水", r#"{"t":"水","r":"`水~","c":4,"n":0}"#),
+            ("忙", r#"{"t":"忙","r":"`心~","c":6,"n":3}"#),
+            ("志", r#"{"t":"志","r":"`心~","c":7,"n":3}"#),
+            ("〇", r#"{"t":"〇"}"#),
+        ]
+        .iter()
+        .map(|(word, entry)| (word.to_string(), serde_json::from_str(entry).unwrap()))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        group_radicals(strokes()),
+        vec![
+            vec![],
+            vec![],
+            vec![],
+            vec!["口".to_string()],
+            vec!["戈".to_string(), "水".to_string(), "心".to_string()]
+        ]
+    );
+    assert_eq!(
+        group_radical_characters(strokes(), "戈"),
+        vec![
+            vec!["戈".to_string()],
+            vec!["戊".to_string()],
+            vec![],
+            vec!["我".to_string()]
+        ]
+    );
+    assert!(group_radical_characters(strokes(), "木").is_empty());
+}
+
+#[test]
+fn test_segment_text() {
+    let words = ["今天", "天天", "天氣", "很好", "空穴來風"];
+    let segment = |x: &str| segment_text(x, |word| words.contains(&word));
+
+    assert_eq!(segment("今天天氣很好"), vec!["今天", "天氣", "很好"]);
+    assert_eq!(
+        segment("空穴來風，不是 MP3"),
+        vec!["空穴來風", "，", "不", "是", "MP3"]
+    );
+    assert!(segment("  ").is_empty());
+    assert!(is_chinese_word("空穴來風"));
+    assert!(!is_chinese_word("，"));
+    assert!(!is_chinese_word("MP3"));
+    assert!(!is_chinese_word("2024"));
+}
+
+#[test]
+fn test_rank_suggestions() {
+    let index = [
+        "空穴來風",
+        "空穴来风",
+        "穴來風",
+        "來風",
+        "空前",
+        "老師",
+        "風",
+    ]
+    .map(|x| x.to_string());
+    let variants = ["空穴来风".to_string()];
+
+    assert_eq!(
+        rank_suggestions("空穴來風", &variants, &index),
+        vec!["空穴来风", "穴來風", "來風", "空前", "風"]
+    );
+    assert_eq!(
+        rank_suggestions("空穴來雨", &[], &index),
+        vec!["空穴來風", "空穴来风", "穴來風", "來風", "空前"]
+    );
+    assert!(rank_suggestions("你好", &[], &index).is_empty());
+}
@@ -0,0 +1,478 @@
+use std::{
+    collections::HashMap,
+    fs::{self, create_dir_all, File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use fs2::FileExt;
+use lazy_static::lazy_static;
+use reqwest::header::{HeaderMap, ETAG, LAST_MODIFIED};
+use serde::{de::DeserializeOwned, de::IgnoredAny, Deserialize, Serialize};
+
+use crate::api::MoedictDictionary;
+
+lazy_static! {
+    pub static ref CACHE_PATH_DIRECTORY: PathBuf =
+        dirs_next::cache_dir().unwrap_or_else(|| PathBuf::from("."));
+    pub static ref JYUTPING_CACHE_PATH: PathBuf = CACHE_PATH_DIRECTORY.join("jyutping.db");
+    pub static ref LEGACY_JYUTPING_CACHE_PATH: PathBuf = CACHE_PATH_DIRECTORY.join("jyutping.json");
+    static ref ENTRY_CACHE_DIRECTORY: PathBuf = CACHE_PATH_DIRECTORY.join("meowdict");
+}
+
+pub fn moedict_index_cache_path(dict: MoedictDictionary) -> PathBuf {
+    match dict {
+        MoedictDictionary::Mandarin => CACHE_PATH_DIRECTORY.join("moedict_index.json"),
+        _ => CACHE_PATH_DIRECTORY.join(format!("moedict_index_{}.json", dict.path())),
+    }
+}
+
+/// Directory of the full-text indexes of the offline store and the entry cache
+pub fn grep_index_path(dict: MoedictDictionary) -> PathBuf {
+    match dict {
+        MoedictDictionary::Mandarin => CACHE_PATH_DIRECTORY.join("grep_index"),
+        _ => CACHE_PATH_DIRECTORY.join(format!("grep_index_{}", dict.path())),
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MeowdictCacheConfig {
+    /// Seconds before a cached moedict or WantWords entry is fetched again
+    pub entry_ttl: u64,
+    /// Seconds before the words.hk wordlist and moedict indexes are fetched again
+    pub index_ttl: u64,
+}
+
+impl Default for MeowdictCacheConfig {
+    fn default() -> Self {
+        MeowdictCacheConfig {
+            entry_ttl: 7 * 24 * 60 * 60,
+            index_ttl: 24 * 60 * 60,
+        }
+    }
+}
+
+/// HTTP validators of a downloaded file, keyed by request path in
+/// `CacheValidators`
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CacheValidator {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+pub type CacheValidators = HashMap<String, CacheValidator>;
+
+impl CacheValidator {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.to_string())
+        };
+
+        CacheValidator {
+            etag: get(ETAG),
+            last_modified: get(LAST_MODIFIED),
+        }
+    }
+}
+
+pub enum CacheEntry {
+    Fresh(String),
+    Stale(String),
+    Missing,
+}
+
+/// One file per cached response, named by the hex encoded key
+pub struct EntryCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl EntryCache {
+    pub fn new(name: &str, ttl: u64) -> Self {
+        EntryCache::with_dir(ENTRY_CACHE_DIRECTORY.join(name), ttl)
+    }
+
+    pub fn with_dir(dir: PathBuf, ttl: u64) -> Self {
+        EntryCache {
+            dir,
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> CacheEntry {
+        let path = self.entry_path(key);
+        let value = match fs::read_to_string(&path) {
+            Ok(value) => value,
+            Err(_) => return CacheEntry::Missing,
+        };
+        if serde_json::from_str::<IgnoredAny>(&value).is_err() {
+            fs::remove_file(&path).ok();
+            return CacheEntry::Missing;
+        }
+        let is_fresh = fs::metadata(&path)
+            .and_then(|x| x.modified())
+            .ok()
+            .and_then(|x| SystemTime::now().duration_since(x).ok())
+            .map(|x| x < self.ttl)
+            .unwrap_or(false);
+
+        if is_fresh {
+            CacheEntry::Fresh(value)
+        } else {
+            CacheEntry::Stale(value)
+        }
+    }
+
+    pub fn put(&self, key: &str, value: &str) -> Result<()> {
+        create_dir_all(&self.dir)?;
+
+        write_atomic(&self.entry_path(key), value.as_bytes())
+    }
+
+    /// Every cached `(key, value)`, fresh or stale
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let key = path
+                .file_name()
+                .and_then(|x| x.to_str()?.strip_suffix(".json"))
+                .and_then(decode_entry_name);
+            if let (Some(key), Ok(value)) = (key, fs::read_to_string(&path)) {
+                entries.push((key, value));
+            }
+        }
+
+        entries
+    }
+
+    /// Last time an entry was written or removed
+    pub fn modified(&self) -> Option<SystemTime> {
+        let dir_modified = fs::metadata(&self.dir).and_then(|x| x.modified()).ok();
+
+        disk_usage(&self.dir).2.max(dir_modified)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        let name = key
+            .bytes()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>();
+
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+fn decode_entry_name(name: &str) -> Option<String> {
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|x| u8::from_str_radix(name.get(x..x + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Sibling path for writing `path` before renaming it into place, unique per
+/// process so concurrent writers never share a temp file
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", process::id()));
+
+    path.with_file_name(name)
+}
+
+/// Replace `path` with `content` so readers only ever see the old or the new file
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp_path = temp_path(path);
+    let result = File::create(&tmp_path).and_then(|mut f| {
+        f.write_all(content)?;
+        f.sync_all()
+    });
+    if let Err(e) = result.and_then(|_| fs::rename(&tmp_path, path)) {
+        fs::remove_file(&tmp_path).ok();
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Read a whole-file JSON cache, treating a truncated or corrupt file as missing
+pub fn read_json_cache<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let f = File::open(path).ok()?;
+
+    serde_json::from_reader(BufReader::new(f)).ok()
+}
+
+fn validators_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".meta");
+
+    path.with_file_name(name)
+}
+
+/// Validators stored next to the whole-file cache at `path`
+pub fn read_validators(path: &Path) -> CacheValidators {
+    read_json_cache(&validators_path(path)).unwrap_or_default()
+}
+
+pub fn write_validators(path: &Path, validators: &CacheValidators) -> Result<()> {
+    write_atomic(&validators_path(path), &serde_json::to_vec(validators)?)
+}
+
+/// Mark an unchanged whole-file cache as fresh again, returning its content
+pub fn touch_cache<T>(path: &Path, read: fn(&Path) -> Option<T>) -> Option<T> {
+    let value = read(path)?;
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()))
+        .ok()?;
+
+    Some(value)
+}
+
+/// Exclusive lock on `path` held across processes until dropped
+pub struct CacheLock(File);
+
+impl CacheLock {
+    pub async fn acquire(path: &Path) -> Result<Self> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.with_file_name(name))?;
+        // Only wait for other processes, filesystems without locking fail
+        while let Err(e) = f.try_lock_exclusive() {
+            if e.raw_os_error() != fs2::lock_contended_error().raw_os_error() {
+                return Err(e.into());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok(CacheLock(f))
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        self.0.unlock().ok();
+    }
+}
+
+pub struct CacheStatus {
+    pub name: String,
+    pub path: PathBuf,
+    pub files: u64,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl CacheStatus {
+    fn new(name: &str, path: PathBuf) -> Self {
+        let (files, size, modified) = disk_usage(&path);
+
+        CacheStatus {
+            name: name.to_string(),
+            path,
+            files,
+            size,
+            modified,
+        }
+    }
+}
+
+/// Returns `(files, bytes, last modified)` of a file or a directory tree
+fn disk_usage(path: &Path) -> (u64, u64, Option<SystemTime>) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, 0, None),
+    };
+    if !metadata.is_dir() {
+        return (1, metadata.len(), metadata.modified().ok());
+    }
+
+    let mut usage = (0, 0, None);
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        let (files, size, modified) = disk_usage(&entry.path());
+        usage.0 += files;
+        usage.1 += size;
+        usage.2 = usage.2.max(modified);
+    }
+
+    usage
+}
+
+/// Remove a whole-file cache together with its validators
+pub fn remove_cache_file(path: &Path) -> Result<()> {
+    for path in [validators_path(path), path.to_path_buf()] {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn cache_status() -> Vec<CacheStatus> {
+    let mut status = vec![CacheStatus::new("words.hk", JYUTPING_CACHE_PATH.clone())];
+    for dict in MoedictDictionary::ALL {
+        status.push(CacheStatus::new(
+            &format!("moedict index ({})", dict),
+            moedict_index_cache_path(dict),
+        ));
+    }
+    for dict in MoedictDictionary::ALL {
+        status.push(CacheStatus::new(
+            &format!("moedict entries ({})", dict),
+            ENTRY_CACHE_DIRECTORY.join(format!("moedict_{}", dict.path())),
+        ));
+    }
+    for dict in MoedictDictionary::ALL {
+        status.push(CacheStatus::new(
+            &format!("full-text index ({})", dict),
+            grep_index_path(dict),
+        ));
+    }
+    status.push(CacheStatus::new(
+        "WantWords entries",
+        ENTRY_CACHE_DIRECTORY.join("wantwords"),
+    ));
+
+    status
+}
+
+/// Remove every downloaded cache, keeping offline stores created by `import`
+pub fn clear_cache() -> Result<()> {
+    for path in MoedictDictionary::ALL
+        .into_iter()
+        .map(moedict_index_cache_path)
+        .chain([
+            JYUTPING_CACHE_PATH.clone(),
+            LEGACY_JYUTPING_CACHE_PATH.clone(),
+        ])
+    {
+        remove_cache_file(&path)?;
+    }
+    for path in MoedictDictionary::ALL
+        .into_iter()
+        .map(grep_index_path)
+        .chain([ENTRY_CACHE_DIRECTORY.clone()])
+    {
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_entry_cache() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let cache = EntryCache::with_dir(dir.path().join("moedict_a"), 60);
+    let expired_cache = EntryCache::with_dir(dir.path().join("moedict_a"), 0);
+
+    assert!(matches!(cache.get("我"), CacheEntry::Missing));
+    cache.put("我", r#"{"t":"我"}"#).unwrap();
+    assert!(matches!(cache.get("我"), CacheEntry::Fresh(x) if x == r#"{"t":"我"}"#));
+    assert!(matches!(expired_cache.get("我"), CacheEntry::Stale(x) if x == r#"{"t":"我"}"#));
+    assert!(dir.path().join("moedict_a/e68891.json").exists());
+    assert_eq!(
+        cache.entries(),
+        vec![("我".to_string(), r#"{"t":"我"}"#.to_string())]
+    );
+    assert!(cache.modified().is_some());
+}
+
+#[test]
+fn test_disk_usage() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let cache = EntryCache::with_dir(dir.path().join("moedict_a"), 60);
+    cache.put("我", "1234").unwrap();
+    cache.put("你", "12").unwrap();
+    let (files, size, modified) = disk_usage(dir.path());
+
+    assert_eq!((files, size), (2, 6));
+    assert!(modified.is_some());
+    assert_eq!(disk_usage(&dir.path().join("missing")), (0, 0, None));
+}
+
+#[test]
+fn test_corrupt_cache() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let cache = EntryCache::with_dir(dir.path().to_path_buf(), 60);
+    let path = dir.path().join("index.json");
+    write_atomic(&path, r#"["我","你"]"#.as_bytes()).unwrap();
+
+    assert_eq!(
+        read_json_cache::<Vec<String>>(&path),
+        Some(vec!["我".to_string(), "你".to_string()])
+    );
+    fs::write(&path, r#"["我","#).unwrap();
+    assert_eq!(read_json_cache::<Vec<String>>(&path), None);
+    fs::write(cache.entry_path("我"), r#"{"t":"#).unwrap();
+    assert!(matches!(cache.get("我"), CacheEntry::Missing));
+    assert!(!cache.entry_path("我").exists());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_cache_lock() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("index.json");
+    let lock = CacheLock::acquire(&path).await.unwrap();
+    let waiting = tokio::time::timeout(Duration::from_millis(300), CacheLock::acquire(&path));
+
+    assert!(waiting.await.is_err());
+    drop(lock);
+    assert!(CacheLock::acquire(&path).await.is_ok());
+}
+
+#[test]
+fn test_cache_validators() {
+    use reqwest::header::HeaderValue;
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("index.json");
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+    let mut validators = CacheValidators::new();
+    validators.insert(
+        "a/index.json".to_string(),
+        CacheValidator::from_headers(&headers),
+    );
+    write_validators(&path, &validators).unwrap();
+
+    assert_eq!(read_validators(&path), validators);
+    assert_eq!(validators["a/index.json"].etag.as_deref(), Some("\"abc\""));
+    assert_eq!(validators["a/index.json"].last_modified, None);
+    assert_eq!(touch_cache::<Vec<String>>(&path, read_json_cache), None);
+    write_atomic(&path, b"[]").unwrap();
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert_eq!(
+        touch_cache::<Vec<String>>(&path, read_json_cache),
+        Some(vec![])
+    );
+    assert!(fs::metadata(&path).unwrap().modified().unwrap() > SystemTime::UNIX_EPOCH);
+}
@@ -0,0 +1,308 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use serde::{Deserialize, Serialize};
+
+const JYUTPING_INITIALS: [&str; 19] = [
+    "gw", "kw", "ng", "b", "p", "m", "f", "d", "t", "n", "l", "g", "k", "h", "w", "z", "c", "s",
+    "j",
+];
+const JYUTPING_CODAS: [&str; 6] = ["ng", "m", "n", "p", "t", "k"];
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CantoneseRomanization {
+    #[default]
+    Jyutping,
+    Yale,
+    Ipa,
+    CantonesePinyin,
+}
+
+impl fmt::Display for CantoneseRomanization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CantoneseRomanization::Jyutping => "jyutping",
+            CantoneseRomanization::Yale => "yale",
+            CantoneseRomanization::Ipa => "ipa",
+            CantoneseRomanization::CantonesePinyin => "cantonese-pinyin",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for CantoneseRomanization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "jyutping" => Ok(CantoneseRomanization::Jyutping),
+            "yale" => Ok(CantoneseRomanization::Yale),
+            "ipa" => Ok(CantoneseRomanization::Ipa),
+            "cantonese-pinyin" => Ok(CantoneseRomanization::CantonesePinyin),
+            _ => Err(anyhow!("Unsupport romanization: {}", s)),
+        }
+    }
+}
+
+/// A jyutping syllable such as `gwong2`, split into its parts
+struct JyutpingSyllable<'a> {
+    initial: &'a str,
+    rime: &'a str,
+    tone: u8,
+}
+
+impl<'a> JyutpingSyllable<'a> {
+    fn parse(s: &'a str) -> Option<Self> {
+        let tone = s
+            .chars()
+            .last()?
+            .to_digit(10)
+            .filter(|x| (1..=6).contains(x))? as u8;
+        let body = &s[..s.len() - 1];
+        if body.is_empty() || !body.chars().all(|x| x.is_ascii_lowercase()) {
+            return None;
+        }
+        // Syllabic nasals have no initial
+        let initial = match body {
+            "m" | "ng" => "",
+            _ => JYUTPING_INITIALS
+                .iter()
+                .find(|x| body.starts_with(**x) && body.len() > x.len())
+                .copied()
+                .unwrap_or(""),
+        };
+
+        Some(JyutpingSyllable {
+            initial,
+            rime: &body[initial.len()..],
+            tone,
+        })
+    }
+
+    fn coda(&self) -> &'a str {
+        if matches!(self.rime, "m" | "ng") {
+            return "";
+        }
+        JYUTPING_CODAS
+            .iter()
+            .find(|x| self.rime.ends_with(**x))
+            .copied()
+            .unwrap_or("")
+    }
+
+    fn is_checked(&self) -> bool {
+        matches!(self.coda(), "p" | "t" | "k")
+    }
+}
+
+/// Convert every jyutping syllable in `s`, leaving anything else untouched
+pub fn convert_jyutping(s: &str, romanization: CantoneseRomanization) -> String {
+    if romanization == CantoneseRomanization::Jyutping {
+        return s.to_string();
+    }
+
+    s.split(' ')
+        .map(|x| match JyutpingSyllable::parse(x) {
+            Some(syllable) => match romanization {
+                CantoneseRomanization::Yale => to_yale(&syllable),
+                CantoneseRomanization::Ipa => to_ipa(&syllable),
+                CantoneseRomanization::CantonesePinyin => to_cantonese_pinyin(&syllable),
+                CantoneseRomanization::Jyutping => x.to_string(),
+            },
+            None => x.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn to_yale(syllable: &JyutpingSyllable) -> String {
+    let initial = match syllable.initial {
+        "z" => "j",
+        "c" => "ch",
+        "j" => "y",
+        x => x,
+    };
+    let rime = match syllable.rime {
+        "aa" => "a".to_string(),
+        x => x.replace("oe", "eu").replace("eo", "eu"),
+    };
+    // `jyu` is written `yu`, not `yyu`
+    let initial = if initial == "y" && rime.starts_with('y') {
+        ""
+    } else {
+        initial
+    };
+    let coda = syllable.coda();
+    let nucleus = &rime[..rime.len() - coda.len()];
+    let mark = match syllable.tone {
+        1 => Some('\u{0304}'),
+        2 | 5 => Some('\u{0301}'),
+        4 => Some('\u{0300}'),
+        _ => None,
+    };
+    let low_tone = if syllable.tone >= 4 { "h" } else { "" };
+
+    format!(
+        "{}{}{}{}",
+        initial,
+        add_tone_mark(nucleus, mark),
+        low_tone,
+        coda
+    )
+}
+
+/// Put `mark` on the first vowel of `nucleus`, or on the syllabic nasal
+fn add_tone_mark(nucleus: &str, mark: Option<char>) -> String {
+    let mark = match mark {
+        Some(mark) => mark,
+        None => return nucleus.to_string(),
+    };
+    let position = nucleus.find(['a', 'e', 'i', 'o', 'u']).unwrap_or_default();
+    let mut result = String::new();
+    for (index, c) in nucleus.char_indices() {
+        if index != position {
+            result.push(c);
+            continue;
+        }
+        match compose_tone_mark(c, mark) {
+            Some(composed) => result.push(composed),
+            None => {
+                result.push(c);
+                result.push(mark);
+            }
+        }
+    }
+
+    result
+}
+
+fn compose_tone_mark(c: char, mark: char) -> Option<char> {
+    let marked = match mark {
+        '\u{0304}' => ['ā', 'ē', 'ī', 'ō', 'ū'],
+        '\u{0301}' => ['á', 'é', 'í', 'ó', 'ú'],
+        '\u{0300}' => ['à', 'è', 'ì', 'ò', 'ù'],
+        _ => return None,
+    };
+
+    "aeiou".find(c).map(|x| marked[x])
+}
+
+fn to_cantonese_pinyin(syllable: &JyutpingSyllable) -> String {
+    let initial = match syllable.initial {
+        "z" => "dz",
+        "c" => "ts",
+        x => x,
+    };
+    let rime = match syllable.rime {
+        "eoi" => "oey".to_string(),
+        x if x.starts_with("yu") => x.replacen("yu", "y", 1),
+        x => x.replace("eo", "oe"),
+    };
+    let tone = match syllable.tone {
+        1 if syllable.is_checked() => 7,
+        3 if syllable.is_checked() => 8,
+        6 if syllable.is_checked() => 9,
+        x => x,
+    };
+
+    format!("{}{}{}", initial, rime, tone)
+}
+
+fn to_ipa(syllable: &JyutpingSyllable) -> String {
+    let initial = match syllable.initial {
+        "b" => "p",
+        "p" => "pʰ",
+        "d" => "t",
+        "t" => "tʰ",
+        "g" => "k",
+        "k" => "kʰ",
+        "ng" => "ŋ",
+        "gw" => "kʷ",
+        "kw" => "kʷʰ",
+        "z" => "ts",
+        "c" => "tsʰ",
+        x => x,
+    };
+    let coda = syllable.coda();
+    let rime = match syllable.rime {
+        "m" => "m̩".to_string(),
+        "ng" => "ŋ̍".to_string(),
+        "ing" => "eŋ".to_string(),
+        "ik" => "ek̚".to_string(),
+        "ung" => "oŋ".to_string(),
+        "uk" => "ok̚".to_string(),
+        "oi" => "ɔːy".to_string(),
+        "ui" => "uːy".to_string(),
+        "eoi" => "ɵy".to_string(),
+        x => {
+            let nucleus = match &x[..x.len() - coda.len()] {
+                "aa" => "aː",
+                "aai" => "aːi",
+                "aau" => "aːu",
+                "a" => "ɐ",
+                "ai" => "ɐi",
+                "au" => "ɐu",
+                "e" => "ɛː",
+                "eu" => "ɛːu",
+                "i" => "iː",
+                "iu" => "iːu",
+                "o" => "ɔː",
+                "u" => "uː",
+                "oe" => "œː",
+                "eo" => "ɵ",
+                "yu" => "yː",
+                nucleus => nucleus,
+            };
+            let coda = match coda {
+                "ng" => "ŋ",
+                "p" => "p̚",
+                "t" => "t̚",
+                "k" => "k̚",
+                coda => coda,
+            };
+            format!("{}{}", nucleus, coda)
+        }
+    };
+    let tone = match syllable.tone {
+        1 => "˥",
+        2 => "˧˥",
+        3 => "˧",
+        4 => "˨˩",
+        5 => "˩˧",
+        _ => "˨",
+    };
+
+    format!("{}{}{}", initial, rime, tone)
+}
+
+#[test]
+fn test_convert_jyutping() {
+    let convert = |s, romanization| convert_jyutping(s, romanization);
+    let yale = CantoneseRomanization::Yale;
+    let ipa = CantoneseRomanization::Ipa;
+    let cantonese_pinyin = CantoneseRomanization::CantonesePinyin;
+
+    assert_eq!(convert("nei5 hou2", yale), "néih hóu");
+    assert_eq!(convert("gwong2 dung1 waa2", yale), "gwóng dūng wá");
+    assert_eq!(convert("jan4 sing4 jyu4", yale), "yàhn sìhng yùh");
+    assert_eq!(convert("zoeng1 ceot1 m4", yale), "jēung chēut m̀h");
+    assert_eq!(convert("sik6 faan6", yale), "sihk faahn");
+    assert_eq!(convert("ngo5 dei6 ， hai6", yale), "ngóh deih ， haih");
+    assert_eq!(convert("gwong2 zau1", ipa), "kʷɔːŋ˧˥ tsɐu˥");
+    assert_eq!(convert("sik6 jyut6 ping3", ipa), "sek̚˨ jyːt̚˨ pʰeŋ˧");
+    assert_eq!(convert("ceot1 heoi3 ng5", ipa), "tsʰɵt̚˥ hɵy˧ ŋ̍˩˧");
+    assert_eq!(
+        convert("zoeng1 jyu4 sik1", cantonese_pinyin),
+        "dzoeng1 jy4 sik7"
+    );
+    assert_eq!(
+        convert("ceot1 heoi3 baat3", cantonese_pinyin),
+        "tsoet7 hoey3 baat8"
+    );
+    assert_eq!(
+        convert("ngo5", CantoneseRomanization::Jyutping),
+        "ngo5".to_string()
+    );
+}
@@ -1,4 +1,4 @@
-use clap::{Command, Arg};
+use clap::{builder::PossibleValue, Arg, ArgAction, Command};
 
 pub fn build_cli() -> Command {
     Command::new("meowdict")
@@ -10,30 +10,52 @@ pub fn build_cli() -> Command {
                 .short('i')
                 .long("input-s2t")
                 .help("Convert input to traditional Chinese and search")
+                .action(ArgAction::SetTrue)
                 .requires("INPUT"),
         )
         .arg(
             Arg::new("resultt2s")
                 .short('r')
                 .long("result-t2s")
-                .help("Convert result to Simplified Chinese to display"),
+                .help("Convert result to Simplified Chinese to display")
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("inputs2tmode")
                 .long("input-s2t-mode")
-                .help("Open console with input-s2t mode"),
+                .help("Open console with input-s2t mode")
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("resultt2smode")
                 .long("result-t2s-mode")
-                .help("Open console with result-t2s mode"),
+                .help("Open console with result-t2s mode")
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("no-color-output")
                 .long("no-color-output")
                 .help("Print result with no color")
+                .action(ArgAction::SetTrue)
                 .requires("INPUT"),
         )
+        .arg(dict_arg())
+        .arg(romanization_arg())
+        .arg(pinyin_style_arg())
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .help("Only use offline store and caches, never touch the network")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("hyperlinks")
+                .long("hyperlinks")
+                .help("Make linked words clickable moedict.tw links in supporting terminals")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .arg(
             Arg::new("INPUT")
                 .help("Input the keyword to use")
@@ -43,12 +65,178 @@ pub fn build_cli() -> Command {
         .subcommand(
             Command::new("show")
                 .about("Get dict result")
+                .arg(dict_arg())
+                .arg(pinyin_style_arg())
                 .arg(
                     Arg::new("INPUT")
                         .help("Input the keyword to use")
                         .index(1)
                         .num_args(1..)
-                        .action(clap::ArgAction::Set)
+                        .action(ArgAction::Set)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inputs2t")
+                        .short('i')
+                        .long("input-s2t")
+                        .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("resultt2s")
+                        .short('r')
+                        .long("result-t2s")
+                        .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("no-color-output")
+                        .long("no-color-output")
+                        .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search headwords with wildcards or patterns")
+                .arg(dict_arg())
+                .arg(pinyin_style_arg())
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Pattern such as 空?來風, *來風 or ^一.{3}$")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("min-length")
+                        .long("min-length")
+                        .help("Only match headwords of at least this many characters")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("max-length")
+                        .long("max-length")
+                        .help("Only match headwords of at most this many characters")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("page")
+                        .short('p')
+                        .long("page")
+                        .help("Page of matches to show [default: 1]")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("per-page")
+                        .long("per-page")
+                        .help("Number of matches on each page [default: 50]")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("full")
+                        .long("full")
+                        .help("Show dict result of the matches on the page")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("inputs2t")
+                        .short('i')
+                        .long("input-s2t")
+                        .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("resultt2s")
+                        .short('r')
+                        .long("result-t2s")
+                        .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("no-color-output")
+                        .long("no-color-output")
+                        .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                ),
+        )
+        .subcommand(
+            Command::new("grep")
+                .about("Search definitions, quotes and examples of entries available offline")
+                .arg(dict_arg())
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Words every matching entry contains")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("field")
+                        .short('f')
+                        .long("field")
+                        .help("Only search these fields [default: all]")
+                        .value_parser(["def", "quote", "example"])
+                        .value_delimiter(',')
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("page")
+                        .short('p')
+                        .long("page")
+                        .help("Page of matches to show [default: 1]")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("per-page")
+                        .long("per-page")
+                        .help("Number of matches on each page [default: 50]")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("inputs2t")
+                        .short('i')
+                        .long("input-s2t")
+                        .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("resultt2s")
+                        .short('r')
+                        .long("result-t2s")
+                        .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("no-color-output")
+                        .long("no-color-output")
+                        .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                ),
+        )
+        .subcommand(
+            Command::new("segment")
+                .alias("seg")
+                .about("Split running text into words and get dict result of each")
+                .arg(dict_arg())
+                .arg(pinyin_style_arg())
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Input text to segment")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
                         .required(true),
                 )
                 .arg(
@@ -56,6 +244,7 @@ pub fn build_cli() -> Command {
                         .short('i')
                         .long("input-s2t")
                         .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
@@ -63,12 +252,14 @@ pub fn build_cli() -> Command {
                         .short('r')
                         .long("result-t2s")
                         .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
                     Arg::new("no-color-output")
                         .long("no-color-output")
                         .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 ),
         )
@@ -76,10 +267,11 @@ pub fn build_cli() -> Command {
             Command::new("translate")
                 .alias("trans")
                 .about("Get word translation")
+                .arg(dict_arg())
                 .arg(
                     Arg::new("INPUT")
                         .help("Input word here")
-                        .action(clap::ArgAction::Set)
+                        .action(ArgAction::Set)
                         .required(true),
                 )
                 .arg(
@@ -87,6 +279,7 @@ pub fn build_cli() -> Command {
                         .short('i')
                         .long("input-s2t")
                         .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
@@ -94,12 +287,14 @@ pub fn build_cli() -> Command {
                         .short('r')
                         .long("result-t2s")
                         .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
                     Arg::new("no-color-output")
                         .long("no-color-output")
                         .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 ),
         )
@@ -107,10 +302,11 @@ pub fn build_cli() -> Command {
             Command::new("jyutping")
                 .alias("jyut")
                 .about("Get word jyutping")
+                .arg(romanization_arg())
                 .arg(
                     Arg::new("INPUT")
                         .help("Input word here")
-                        .action(clap::ArgAction::Set)
+                        .action(ArgAction::Set)
                         .required(true),
                 )
                 .arg(
@@ -118,6 +314,7 @@ pub fn build_cli() -> Command {
                         .short('i')
                         .long("input-s2t")
                         .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
@@ -125,12 +322,14 @@ pub fn build_cli() -> Command {
                         .short('r')
                         .long("result-t2s")
                         .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
                     Arg::new("no-color-output")
                         .long("no-color-output")
                         .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 ),
         )
@@ -138,33 +337,41 @@ pub fn build_cli() -> Command {
             Command::new("terminal")
                 .alias("term")
                 .about("Open meowdict terminal")
+                .arg(dict_arg())
+                .arg(romanization_arg())
+                .arg(pinyin_style_arg())
                 .arg(
                     Arg::new("inputs2tmode")
                         .long("input-s2t-mode")
-                        .help("Open console with input-s2t mode"),
+                        .help("Open console with input-s2t mode")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
                     Arg::new("resultt2smode")
                         .long("result-t2s-mode")
-                        .help("Open console with result-t2s mode"),
+                        .help("Open console with result-t2s mode")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
                     Arg::new("no-color-output")
                         .long("no-color-output")
                         .help("Print result with no color")
-                        .requires("INPUT"),
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
             Command::new("random")
                 .alias("rand")
                 .about("search random word")
+                .arg(dict_arg())
+                .arg(pinyin_style_arg())
                 .arg(Arg::new("INPUT").help("Input word here").num_args(0..))
                 .arg(
                     Arg::new("inputs2t")
                         .short('i')
                         .long("input-s2t")
                         .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
@@ -172,22 +379,202 @@ pub fn build_cli() -> Command {
                         .short('r')
                         .long("result-t2s")
                         .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
                     Arg::new("no-color-output")
                         .long("no-color-output")
                         .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                ),
+        )
+        .subcommand(
+            Command::new("radical")
+                .alias("rad")
+                .about("List radicals, or characters of a radical by stroke count")
+                .arg(dict_arg())
+                .arg(pinyin_style_arg())
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Radical to list characters of")
+                        .num_args(0..=1),
+                )
+                .arg(
+                    Arg::new("open")
+                        .short('o')
+                        .long("open")
+                        .help("Show the character with this number in the list")
+                        .value_parser(clap::value_parser!(usize))
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("inputs2t")
+                        .short('i')
+                        .long("input-s2t")
+                        .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("resultt2s")
+                        .short('r')
+                        .long("result-t2s")
+                        .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("no-color-output")
+                        .long("no-color-output")
+                        .help("Print result with no color")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("reverse")
+                .alias("rev")
+                .about("Search words by description")
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Input description here")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("all")
+                        .short('a')
+                        .long("all")
+                        .help("Also show words with zero correlation")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print result to JSON output")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("inputs2t")
+                        .short('i')
+                        .long("input-s2t")
+                        .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("resultt2s")
+                        .short('r')
+                        .long("result-t2s")
+                        .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                )
+                .arg(
+                    Arg::new("no-color-output")
+                        .long("no-color-output")
+                        .help("Print result with no color")
+                        .action(ArgAction::SetTrue)
+                        .requires("INPUT"),
+                ),
+        )
+        .subcommand(
+            Command::new("romanize")
+                .alias("rom")
+                .about("Convert Mandarin readings between pinyin, zhuyin and other romanizations")
+                .arg(
+                    Arg::new("INPUT")
+                        .help("Input syllables here")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
+                        .required(true),
+                )
+                .arg(
+                    style_arg("from")
+                        .short('f')
+                        .help("Read input in this style instead of guessing it"),
+                )
+                .arg(
+                    style_arg("to")
+                        .short('t')
+                        .help("Style to convert to [default: numbered]"),
+                ),
+        )
+        .subcommand(
+            Command::new("annotate")
+                .alias("anno")
+                .about("Annotate text with pinyin, zhuyin or jyutping")
+                .arg(dict_arg())
+                .arg(romanization_arg())
+                .arg(
+                    Arg::new("FILE")
+                        .help("Path to text file, read from stdin when missing or -")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("reading")
+                        .long("reading")
+                        .help("Choose reading to annotate with [default: pinyin]")
+                        .value_parser(["pinyin", "zhuyin", "jyutping"])
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Choose output format [default: interlinear]")
+                        .value_parser(["html", "markdown", "interlinear"])
+                        .action(ArgAction::Set),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Import moedict JSON dump for offline lookup")
+                .arg(dict_arg())
+                .arg(
+                    Arg::new("FILE")
+                        .help("Path to moedict JSON dump")
+                        .action(ArgAction::Set)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Inspect and manage local caches")
+                .subcommand(Command::new("status").about("Show cache locations, sizes and ages"))
+                .subcommand(
+                    Command::new("refresh")
+                        .about("Download words.hk wordlist and moedict index again")
+                        .arg(dict_arg()),
+                )
+                .subcommand(Command::new("clear").about("Remove downloaded caches"))
+                .subcommand(
+                    Command::new("prefetch")
+                        .about("Cache moedict entries of every word in a file")
+                        .arg(dict_arg())
+                        .arg(
+                            Arg::new("FILE")
+                                .help("Path to whitespace separated word list")
+                                .action(ArgAction::Set)
+                                .required(true),
+                        ),
                 ),
         )
         .subcommand(
             Command::new("json")
                 .about("Print result to JSON output")
+                .arg(dict_arg())
+                .arg(romanization_arg())
                 .arg(
                     Arg::new("INPUT")
-                        .help("Input word here")
-                        .action(clap::ArgAction::Set)
+                        .help("Input the keyword to use")
+                        .index(1)
+                        .num_args(1..)
+                        .action(ArgAction::Set)
                         .required(true),
                 )
                 .arg(
@@ -195,6 +582,7 @@ pub fn build_cli() -> Command {
                         .short('i')
                         .long("input-s2t")
                         .help("Convert input to traditional Chinese and search")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 )
                 .arg(
@@ -202,7 +590,47 @@ pub fn build_cli() -> Command {
                         .short('r')
                         .long("result-t2s")
                         .help("Convert result to Simplified Chinese to display")
+                        .action(ArgAction::SetTrue)
                         .requires("INPUT"),
                 ),
         )
 }
+
+fn dict_arg() -> Arg {
+    Arg::new("dict")
+        .short('d')
+        .long("dict")
+        .help("Choose moedict dictionary to search")
+        .value_parser([
+            PossibleValue::new("mandarin").alias("a"),
+            PossibleValue::new("taiwanese").alias("t"),
+            PossibleValue::new("hakka").alias("h"),
+            PossibleValue::new("cross-strait").alias("c"),
+        ])
+        .action(ArgAction::Set)
+}
+
+fn romanization_arg() -> Arg {
+    Arg::new("romanization")
+        .long("romanization")
+        .help("Choose Cantonese romanization of jyutping results")
+        .value_parser(["jyutping", "yale", "ipa", "cantonese-pinyin"])
+        .action(ArgAction::Set)
+}
+
+fn pinyin_style_arg() -> Arg {
+    style_arg("pinyin-style").help("Choose how Mandarin pinyin is written")
+}
+
+fn style_arg(id: &'static str) -> Arg {
+    Arg::new(id)
+        .long(id)
+        .value_parser([
+            PossibleValue::new("pinyin"),
+            PossibleValue::new("numbered"),
+            PossibleValue::new("zhuyin").alias("bopomofo"),
+            PossibleValue::new("wade-giles"),
+            PossibleValue::new("gwoyeu-romatzyh"),
+        ])
+        .action(ArgAction::Set)
+}
@@ -0,0 +1,264 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use reqwest::{
+    header::{HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    Client, Response,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{CacheValidator, MeowdictCacheConfig};
+
+const MOEDICT_URL: &str = "https://www.moedict.tw";
+const WORDSHK_URL: &str = "https://words.hk/faiman/analysis";
+const WANTWORDS_URL: &str = "https://wantwords.thunlp.org/ChineseRD";
+
+/// Ordered mirror lists for every backend, tried from first to last
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MeowdictBackends {
+    pub moedict: Vec<String>,
+    pub wordshk: Vec<String>,
+    pub wantwords: Vec<String>,
+}
+
+impl Default for MeowdictBackends {
+    fn default() -> Self {
+        MeowdictBackends {
+            moedict: vec![MOEDICT_URL.to_string()],
+            wordshk: vec![WORDSHK_URL.to_string()],
+            wantwords: vec![WANTWORDS_URL.to_string()],
+        }
+    }
+}
+
+impl MeowdictBackends {
+    /// Override mirrors with comma separated lists from `MEOWDICT_MOEDICT_URL`,
+    /// `MEOWDICT_WORDSHK_URL` and `MEOWDICT_WANTWORDS_URL`
+    pub fn with_env(mut self) -> Self {
+        for (name, mirrors) in [
+            ("MEOWDICT_MOEDICT_URL", &mut self.moedict),
+            ("MEOWDICT_WORDSHK_URL", &mut self.wordshk),
+            ("MEOWDICT_WANTWORDS_URL", &mut self.wantwords),
+        ] {
+            if let Some(value) = env::var(name).ok().and_then(|x| parse_mirror_list(&x)) {
+                *mirrors = value;
+            }
+        }
+
+        self
+    }
+}
+
+fn parse_mirror_list(s: &str) -> Option<Vec<String>> {
+    let mirrors = s
+        .split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+
+    if mirrors.is_empty() {
+        None
+    } else {
+        Some(mirrors)
+    }
+}
+
+pub struct MeowdictClient {
+    pub client: Client,
+    pub backends: MeowdictBackends,
+    pub cache: MeowdictCacheConfig,
+    pub offline: bool,
+}
+
+impl MeowdictClient {
+    pub fn new(backends: MeowdictBackends, cache: MeowdictCacheConfig, offline: bool) -> Self {
+        MeowdictClient {
+            client: Client::new(),
+            backends,
+            cache,
+            offline,
+        }
+    }
+
+    /// GET `path` from the first mirror that is reachable and does not answer
+    /// with a server error
+    pub async fn get(
+        &self,
+        mirrors: &[String],
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Response> {
+        self.send(mirrors, path, query, HeaderMap::new()).await
+    }
+
+    /// Like `get`, but lets the server answer 304 Not Modified when `validator`
+    /// still matches
+    pub async fn get_conditional(
+        &self,
+        mirrors: &[String],
+        path: &str,
+        validator: &CacheValidator,
+    ) -> Result<Response> {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = validator.etag.as_deref().and_then(|x| x.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = validator
+            .last_modified
+            .as_deref()
+            .and_then(|x| x.parse().ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        self.send(mirrors, path, &[], headers).await
+    }
+
+    async fn send(
+        &self,
+        mirrors: &[String],
+        path: &str,
+        query: &[(&str, &str)],
+        headers: HeaderMap,
+    ) -> Result<Response> {
+        if self.offline {
+            return Err(anyhow!("Cannot request {} in offline mode", path));
+        }
+        let mut last_error = anyhow!("No mirror configured for {}", path);
+        for mirror in mirrors {
+            let url = format!("{}/{}", mirror.trim_end_matches('/'), path);
+            let request = self.client.get(&url).query(query).headers(headers.clone());
+            match request.send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    last_error = anyhow!("{}: Response status code: {}", url, response.status());
+                }
+                Ok(response) => return Ok(response),
+                Err(e) => last_error = e.into(),
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+#[tokio::test]
+async fn test_mirror_failover() {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+    let serve = |status: &'static str, body: &'static str| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut [0u8; 16]).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        url
+    };
+    let unreachable = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+    let client = MeowdictClient::new(
+        MeowdictBackends::default(),
+        MeowdictCacheConfig::default(),
+        false,
+    );
+    let mirrors = vec![
+        unreachable,
+        serve("503 Service Unavailable", "down"),
+        serve("200 OK", "up"),
+    ];
+    let response = client.get(&mirrors, "index.json", &[]).await.unwrap();
+
+    assert_eq!(response.text().await.unwrap(), "up");
+}
+
+#[tokio::test]
+async fn test_offline_client() {
+    let client = MeowdictClient::new(
+        MeowdictBackends::default(),
+        MeowdictCacheConfig::default(),
+        true,
+    );
+
+    assert!(client
+        .get(&["http://127.0.0.1:1".to_string()], "index.json", &[])
+        .await
+        .is_err());
+}
+
+#[test]
+fn test_parse_mirror_list() {
+    assert_eq!(
+        parse_mirror_list("http://a.example, http://b.example/,"),
+        Some(vec![
+            "http://a.example".to_string(),
+            "http://b.example/".to_string()
+        ])
+    );
+    assert_eq!(parse_mirror_list(" , "), None);
+}
+
+#[tokio::test]
+async fn test_conditional_request() {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers.push(line.trim().to_lowercase());
+        }
+        let status = if headers.contains(&"if-none-match: \"v1\"".to_string()) {
+            "304 Not Modified"
+        } else {
+            "200 OK"
+        };
+        write!(
+            &stream,
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        )
+        .unwrap();
+    });
+    let client = MeowdictClient::new(
+        MeowdictBackends::default(),
+        MeowdictCacheConfig::default(),
+        false,
+    );
+    let validator = CacheValidator {
+        etag: Some("\"v1\"".to_string()),
+        last_modified: None,
+    };
+    let response = client
+        .get_conditional(&[url], "index.json", &validator)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 304);
+}
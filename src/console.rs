@@ -1,16 +1,64 @@
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
-use reqwest::Client;
 use rustyline::{config::Configurer, DefaultEditor};
 
+use crate::api::MoedictDictionary;
+use crate::cantonese::CantoneseRomanization;
+use crate::client::MeowdictClient;
 use crate::feat::*;
-use crate::formatter::OpenccConvertMode;
+use crate::formatter::{gen_links_str, OpenccConvertMode};
+use crate::mandarin::PinyinStyle;
 
 pub struct MeowdictConsole<'a> {
-    pub client: &'a Client,
+    pub client: &'a MeowdictClient,
     pub input_s2t: bool,
     pub result_t2s: bool,
     pub no_color: bool,
+    pub dict: MoedictDictionary,
+    pub romanization: CantoneseRomanization,
+    pub pinyin_style: Option<PinyinStyle>,
+    pub hyperlinks: bool,
+    /// Numbered links or hits of the last result, opened with `.go N`
+    pub links: Vec<String>,
+    pub history: NavigationHistory,
+}
+
+/// Shown queries, walked like browser history with `.back` and `.forward`
+#[derive(Default)]
+pub struct NavigationHistory {
+    back: Vec<Vec<String>>,
+    current: Option<Vec<String>>,
+    forward: Vec<Vec<String>>,
+}
+
+impl NavigationHistory {
+    pub fn visit(&mut self, words: Vec<String>) {
+        if self.current.as_ref() == Some(&words) {
+            return;
+        }
+        if let Some(current) = self.current.replace(words) {
+            self.back.push(current);
+        }
+        self.forward.clear();
+    }
+
+    pub fn back(&mut self) -> Option<Vec<String>> {
+        let words = self.back.pop()?;
+        if let Some(current) = self.current.replace(words.clone()) {
+            self.forward.push(current);
+        }
+
+        Some(words)
+    }
+
+    pub fn forward(&mut self) -> Option<Vec<String>> {
+        let words = self.forward.pop()?;
+        if let Some(current) = self.current.replace(words.clone()) {
+            self.back.push(current);
+        }
+
+        Some(words)
+    }
 }
 
 lazy_static! {
@@ -29,11 +77,29 @@ const USAGE: &str = r#"Usage:
 .jyut(jyutping) [WORDS]
 .rev(reverse) [WORDS]
 .rev(reverse) .all [WORDS]
+.rev(reverse) .json [WORDS]
+.radical
+.radical [RADICAL]
+.radical [RADICAL] [NUMBER]
+.seg(segment) [TEXT]
+.search [PATTERN]
+.search [PATTERN] [PAGE]
+.search .full [PATTERN]
+.grep [WORDS]
+.grep .def(.quote|.example) [WORDS]
+.json [WORDS]
 .trans(translate) [WORDS]
 .show .input_s2t [WORDS]
 .show .result_t2s [WORDS]
 .set_input_s2t_mode [on|off]
 .set_result_t2s_mode [on|off]
+.dict [mandarin|taiwanese|hakka|cross-strait]
+.romanization [jyutping|yale|ipa|cantonese-pinyin]
+.pinyin_style [pinyin|numbered|zhuyin|wade-giles|gwoyeu-romatzyh]
+.go(link)
+.go(link) [NUMBER]
+.back
+.forward
 "#;
 
 macro_rules! set_run_status {
@@ -66,7 +132,6 @@ impl MeowdictConsole<'_> {
         reader.set_auto_add_history(true);
         while let Ok(argument) = reader.readline("meowdict > ") {
             let argument = argument
-                .trim()
                 .split_whitespace()
                 .filter(|x| x != &"")
                 .collect::<Vec<&str>>();
@@ -105,7 +170,12 @@ impl MeowdictConsole<'_> {
         let mut command_result_t2s = false;
         let mut command_input_s2t = false;
         let mut is_all = false;
+        let mut json = false;
         let mut run_status: Option<MeowdictRunCommand> = None;
+        let mut target = None;
+        let mut from_history = false;
+        let mut search = SearchOptions::default();
+        let mut open = None;
         if args.is_empty() && !values.is_empty() {
             set_run_status!(run_status, MeowdictRunCommand::Show);
         }
@@ -132,12 +202,115 @@ impl MeowdictConsole<'_> {
                 ".set_result_t2s_mode" => {
                     set_run_status_mode!(&OpenccConvertMode::T2S, values, run_status, self);
                 }
+                ".dict" => {
+                    if run_status.is_some() {
+                        return Err(anyhow!("Cannot perform multiple queries!"));
+                    }
+                    match values.first() {
+                        Some(dict) => {
+                            self.dict = dict.parse()?;
+                            println!("Setting dictionary to {}...", dict);
+                        }
+                        None => println!("Current dictionary: {}", self.dict),
+                    }
+
+                    return Ok(());
+                }
+                ".romanization" => {
+                    if run_status.is_some() {
+                        return Err(anyhow!("Cannot perform multiple queries!"));
+                    }
+                    match values.first() {
+                        Some(romanization) => {
+                            self.romanization = romanization.parse()?;
+                            println!("Setting romanization to {}...", romanization);
+                        }
+                        None => println!("Current romanization: {}", self.romanization),
+                    }
+
+                    return Ok(());
+                }
+                ".pinyin_style" => {
+                    if run_status.is_some() {
+                        return Err(anyhow!("Cannot perform multiple queries!"));
+                    }
+                    match values.first() {
+                        Some(style) => {
+                            self.pinyin_style = Some(style.parse()?);
+                            println!("Setting pinyin style to {}...", style);
+                        }
+                        None => println!(
+                            "Current pinyin style: {}",
+                            self.pinyin_style.unwrap_or_default()
+                        ),
+                    }
+
+                    return Ok(());
+                }
+                ".go" | ".link" => {
+                    if run_status.is_some() {
+                        return Err(anyhow!("Cannot perform multiple queries!"));
+                    }
+                    match values.first() {
+                        Some(number) => {
+                            let word = number
+                                .parse::<usize>()
+                                .ok()
+                                .and_then(|x| x.checked_sub(1))
+                                .and_then(|x| self.links.get(x))
+                                .ok_or_else(|| anyhow!("Invaild link number: {}", number))?;
+                            target = Some(vec![word.clone()]);
+                            run_status = Some(MeowdictRunCommand::Show);
+                        }
+                        None => {
+                            println!("{}", gen_links_str(&self.links, get_terminal_size()));
+                            return Ok(());
+                        }
+                    }
+                }
+                ".back" | ".forward" => {
+                    if run_status.is_some() {
+                        return Err(anyhow!("Cannot perform multiple queries!"));
+                    }
+                    let words = if arg == ".back" {
+                        self.history.back()
+                    } else {
+                        self.history.forward()
+                    };
+                    target = Some(words.ok_or_else(|| anyhow!("No more history!"))?);
+                    from_history = true;
+                    run_status = Some(MeowdictRunCommand::Show);
+                }
                 ".random" | ".rand" => {
                     set_run_status!(run_status, MeowdictRunCommand::Random);
                 }
+                ".reverse" | ".rev" => {
+                    set_run_status!(run_status, MeowdictRunCommand::Reverse);
+                }
+                ".segment" | ".seg" => {
+                    set_run_status!(run_status, MeowdictRunCommand::Segment);
+                }
+                ".search" => {
+                    set_run_status!(run_status, MeowdictRunCommand::Search);
+                }
+                ".grep" => {
+                    set_run_status!(run_status, MeowdictRunCommand::Grep);
+                }
+                ".def" | ".quote" | ".example" => {
+                    search.fields.push(arg[1..].parse()?);
+                }
+                ".full" => {
+                    search.full = true;
+                }
+                ".radical" => {
+                    set_run_status!(run_status, MeowdictRunCommand::Radical);
+                }
                 ".all" => {
                     is_all = true;
                 }
+                ".json" => {
+                    json = true;
+                }
                 ".help" => {
                     println!("{}", USAGE);
                 }
@@ -149,16 +322,40 @@ impl MeowdictConsole<'_> {
         if run_status.is_none() && !values.is_empty() {
             run_status = Some(MeowdictRunCommand::Show);
         }
+        if json {
+            run_status = match run_status {
+                Some(MeowdictRunCommand::Show) => Some(MeowdictRunCommand::Json),
+                Some(MeowdictRunCommand::Reverse) | None => run_status,
+                _ => return Err(anyhow!("Cannot print this query to JSON output!")),
+            };
+        }
         let input_s2t = command_input_s2t || self.input_s2t;
         let result_t2s = command_result_t2s || self.result_t2s;
         let no_color = self.no_color;
-        let words = if !values.is_empty() {
+        let mut values = values;
+        if let (Some(MeowdictRunCommand::Search), [_, page]) = (&run_status, values.as_slice()) {
+            search.page = page
+                .parse()
+                .map_err(|_| anyhow!("Invaild page number: {}", page))?;
+            values.pop();
+        }
+        if let (Some(MeowdictRunCommand::Radical), [_, number]) = (&run_status, values.as_slice()) {
+            open = Some(
+                number
+                    .parse()
+                    .map_err(|_| anyhow!("Invaild character number: {}", number))?,
+            );
+            values.pop();
+        }
+        let words = if target.is_some() {
+            target
+        } else if !values.is_empty() {
             Some(values)
         } else {
             None
         };
         if let Some(run_status) = run_status {
-            MeowdictResponse {
+            let mut response = MeowdictResponse {
                 command: run_status,
                 client: self.client,
                 input_s2t,
@@ -166,9 +363,38 @@ impl MeowdictConsole<'_> {
                 no_color,
                 words,
                 is_all,
+                json,
+                dict: self.dict,
+                pinyin_style: self.pinyin_style,
+                romanization: self.romanization,
+                hyperlinks: self.hyperlinks,
+                numbered: true,
+                references: Vec::new(),
+                search,
+            };
+            match open {
+                Some(number) => response.open_radical_character(number).await?,
+                None => response.match_command_to_run().await?,
+            }
+            // Random and opened radical characters leave the shown entries
+            // in `words`
+            if let (MeowdictRunCommand::Show | MeowdictRunCommand::Random, Some(words), false) =
+                (&response.command, response.words.clone(), from_history)
+            {
+                self.history.visit(words);
+            }
+            if matches!(
+                response.command,
+                MeowdictRunCommand::Show
+                    | MeowdictRunCommand::Random
+                    | MeowdictRunCommand::Reverse
+                    | MeowdictRunCommand::Radical
+                    | MeowdictRunCommand::Segment
+                    | MeowdictRunCommand::Search
+                    | MeowdictRunCommand::Grep
+            ) {
+                self.links = response.references;
             }
-            .match_command_to_run()
-            .await?;
         }
 
         Ok(())
@@ -201,3 +427,21 @@ fn test_argument_splitter() {
     assert_eq!(vec![".jyut"], command);
     assert_eq!(vec!["我"], values);
 }
+
+#[test]
+fn test_navigation_history() {
+    let words = |x: &str| vec![x.to_string()];
+    let mut history = NavigationHistory::default();
+    history.visit(words("我"));
+    history.visit(words("自"));
+    history.visit(words("自"));
+    history.visit(words("稱"));
+
+    assert_eq!(history.back(), Some(words("自")));
+    assert_eq!(history.back(), Some(words("我")));
+    assert_eq!(history.back(), None);
+    assert_eq!(history.forward(), Some(words("自")));
+    history.visit(words("己"));
+    assert_eq!(history.forward(), None);
+    assert_eq!(history.back(), Some(words("自")));
+}
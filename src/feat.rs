@@ -1,10 +1,18 @@
+use std::{collections::HashSet, fs, path::Path};
+
 use crate::api::*;
+use crate::cache::{cache_status, clear_cache};
+use crate::cantonese::CantoneseRomanization;
+use crate::client::MeowdictClient;
 use crate::formatter::*;
+use crate::grep::{grep_entries, GrepField};
+use crate::mandarin::PinyinStyle;
+use crate::pattern::HeadwordPattern;
 use anyhow::{anyhow, Result};
+use clap::ArgMatches;
 use console::{strip_ansi_codes, Term};
 use opencc_rust::{DefaultConfig, OpenCC};
 use rand::prelude::{IteratorRandom, SliceRandom};
-use reqwest::Client;
 
 pub enum MeowdictRunCommand {
     Show,
@@ -12,6 +20,11 @@ pub enum MeowdictRunCommand {
     JyutPing,
     Json,
     Random,
+    Reverse,
+    Radical,
+    Segment,
+    Search,
+    Grep,
 }
 
 enum OpenccConvertMode {
@@ -21,12 +34,50 @@ enum OpenccConvertMode {
 
 pub struct MeowdictResponse<'a> {
     pub command: MeowdictRunCommand,
-    pub client: &'a Client,
+    pub client: &'a MeowdictClient,
     pub input_s2t: bool,
     pub result_t2s: bool,
     pub no_color: bool,
     pub words: Option<Vec<String>>,
     pub is_all: bool,
+    pub json: bool,
+    pub dict: MoedictDictionary,
+    pub romanization: CantoneseRomanization,
+    pub pinyin_style: Option<PinyinStyle>,
+    pub hyperlinks: bool,
+    /// Number links and hits so the console can open them with `.go N`
+    pub numbered: bool,
+    /// Words linked from the shown entries, or the hits of `Reverse`
+    pub references: Vec<String>,
+    pub search: SearchOptions,
+}
+
+const SEARCH_PAGE_SIZE: usize = 50;
+
+#[derive(Clone)]
+pub struct SearchOptions {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// Starting from 1
+    pub page: usize,
+    pub per_page: usize,
+    /// Show the entries of the hits on the page too
+    pub full: bool,
+    /// Fields `Grep` looks in, all of them when empty
+    pub fields: Vec<GrepField>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            min_length: None,
+            max_length: None,
+            page: 1,
+            per_page: SEARCH_PAGE_SIZE,
+            full: false,
+            fields: Vec::new(),
+        }
+    }
 }
 
 impl MeowdictResponse<'_> {
@@ -38,44 +89,142 @@ impl MeowdictResponse<'_> {
             MeowdictRunCommand::JyutPing => self.search_word_to_jyutping_result().await?,
             MeowdictRunCommand::Json => self.search_word_to_json_result().await?,
             MeowdictRunCommand::Random => self.random_moedict_item().await?,
+            MeowdictRunCommand::Reverse => self.search_word_to_wantwords_result().await?,
+            MeowdictRunCommand::Radical => self.radical_result().await?,
+            MeowdictRunCommand::Segment => self.segment_result().await?,
+            MeowdictRunCommand::Search => self.search_result().await?,
+            MeowdictRunCommand::Grep => self.grep_result()?,
         };
         println!("{}", self.setup_result(&result));
 
         Ok(())
     }
 
-    async fn search_word_to_dict_result(&self) -> Result<String> {
+    async fn search_word_to_dict_result(&mut self) -> Result<String> {
+        let words = self.words()?.to_vec();
+        let result = self.dict_result(&words).await?;
+
+        Ok(self.with_links(result, &self.references, get_terminal_size()))
+    }
+
+    /// Entries of `words`, keeping their references
+    async fn dict_result(&mut self, words: &[String]) -> Result<String> {
+        let terminal_size = get_terminal_size();
+        let meowdict_results = get_dict_result(self.client, words, self.dict).await;
+        let mut meowdict_results = self.with_suggestions(meowdict_results).await;
+        // Taiwanese and Hakka entries carry their own romanizations in `pinyin`
+        if let (Some(style), MoedictDictionary::Mandarin | MoedictDictionary::CrossStrait) =
+            (self.pinyin_style, self.dict)
+        {
+            meowdict_results = meowdict_results
+                .into_iter()
+                .map(|x| x.map(|x| x.romanize_pinyin(style)))
+                .collect();
+        }
+        self.references = collect_references(&meowdict_results);
+
+        Ok(gen_dict_result_str(
+            words,
+            meowdict_results,
+            terminal_size,
+            self.hyperlink_dict(),
+        ))
+    }
+
+    /// Segment the input as running text and show every word of it once,
+    /// leaving out punctuation
+    async fn segment_result(&mut self) -> Result<String> {
+        let text = self.words()?.join(" ");
+        let segments = get_segments(self.client, &text, self.dict).await?;
+        let mut words = Vec::new();
+        for segment in &segments {
+            if is_chinese_word(segment) && !words.contains(segment) {
+                words.push(segment.clone());
+            }
+        }
+        let terminal_size = get_terminal_size();
+        let result = self.dict_result(&words).await?;
+
+        Ok(format!(
+            "{}\n{}",
+            gen_segment_str(&segments, terminal_size),
+            self.with_links(result, &self.references, terminal_size)
+        ))
+    }
+
+    /// Headwords of the moedict index matching the pattern in `words`, one
+    /// page at a time
+    async fn search_result(&mut self) -> Result<String> {
         let terminal_size = get_terminal_size();
-        let meowdict_results = get_dict_result(self.client, self.words.as_ref().unwrap()).await?;
-        let result = gen_dict_result_str(meowdict_results, terminal_size);
+        let input = self.words()?.join(" ");
+        let pattern = input.parse::<HeadwordPattern>()?;
+        let options = self.search.clone();
+        let hits = get_moedict_index(self.client, self.dict)
+            .await?
+            .into_iter()
+            .filter(|x| {
+                let length = x.chars().count();
+                options.min_length.is_none_or(|min| length >= min)
+                    && options.max_length.is_none_or(|max| length <= max)
+                    && pattern.is_match(x)
+            })
+            .collect::<Vec<_>>();
+        let page = SearchPage::new(hits.len(), options.page, options.per_page);
+        let hits = hits[page.range()].to_vec();
+        let mut result = gen_search_str(&input, &hits, &page, self.numbered, terminal_size);
+        if options.full && !hits.is_empty() {
+            result = format!("{}\n{}", result, self.dict_result(&hits).await?);
+        }
+        self.references = hits;
 
         Ok(result)
     }
 
     async fn search_word_to_translation_result(&self) -> Result<String> {
-        let meowdict_results = get_dict_result(self.client, self.words.as_ref().unwrap()).await?;
-        let result = gen_translation_str(meowdict_results);
+        let words = self.words()?;
+        let meowdict_results = get_dict_result(self.client, words, self.dict).await;
+        let result = gen_translation_str(words, meowdict_results);
 
         Ok(result)
     }
 
     async fn search_word_to_jyutping_result(&self) -> Result<String> {
-        let jyutping_results =
-            get_jyutping_result(self.client, self.words.as_ref().unwrap()).await?;
-        let result = gen_jyutping_str(jyutping_results);
+        let words = self.words()?;
+        let jyutping_results = get_jyutping_result(self.client, words).await?;
+        let result = gen_jyutping_str(words, jyutping_results, self.romanization);
 
         Ok(result)
     }
 
     async fn search_word_to_json_result(&self) -> Result<String> {
-        let json_obj = set_json_result(self.client, self.words.as_ref().unwrap()).await;
+        let json_obj =
+            set_json_result(self.client, self.words()?, self.dict, self.romanization).await;
         let result = gen_dict_json_str(json_obj)?;
 
         Ok(result)
     }
 
-    async fn random_moedict_item(&self) -> Result<String> {
-        let moedict_index = get_moedict_index(self.client).await?;
+    async fn search_word_to_wantwords_result(&mut self) -> Result<String> {
+        let words = self.words()?;
+        let wantwords_results = get_wantwords(words, self.client).await;
+        let hits = wantwords_results
+            .iter()
+            .flat_map(|x| &x.words)
+            .filter(|x| self.is_all || !x.is_zero_correlation())
+            .map(|x| x.word.clone())
+            .collect();
+        let result = if self.json {
+            gen_wantwords_json_str(wantwords_results, self.is_all)?
+        } else {
+            gen_wantwords_str(wantwords_results, self.is_all, self.numbered)
+        };
+        self.references = hits;
+
+        Ok(result)
+    }
+
+    async fn random_moedict_item(&mut self) -> Result<String> {
+        let moedict_index = get_moedict_index(self.client, self.dict).await?;
         let rng = &mut rand::thread_rng();
         let terminal_size = get_terminal_size();
         let rand_words = match &self.words {
@@ -101,12 +250,120 @@ impl MeowdictResponse<'_> {
                     .to_owned()]
             }
         };
-        let moedict_results = get_dict_result(self.client, &rand_words).await?;
-        let result = gen_dict_result_str(moedict_results, terminal_size);
+        let result = self.dict_result(&rand_words).await?;
+        // Leave the shown entries behind for the console history
+        self.words = Some(rand_words);
+
+        Ok(self.with_links(result, &self.references, terminal_size))
+    }
+
+    async fn radical_result(&mut self) -> Result<String> {
+        let terminal_size = get_terminal_size();
+        let radical = match self.words.as_deref() {
+            Some([radical, ..]) => radical,
+            _ => {
+                let radicals = get_radicals(self.client, self.dict).await?;
+                return Ok(gen_radicals_str(&radicals, terminal_size));
+            }
+        };
+        let characters = get_radical_characters(self.client, self.dict, radical).await?;
+        let result = gen_radical_characters_str(radical, &characters, self.numbered, terminal_size);
+        self.references = characters.concat();
 
         Ok(result)
     }
 
+    /// Show the `number`th character listed for the radical in `words`
+    pub async fn open_radical_character(&mut self, number: usize) -> Result<()> {
+        self.words = self.words_input_s2t();
+        let radical = self
+            .words()?
+            .first()
+            .ok_or_else(|| anyhow!("Please enter radical!"))?;
+        let characters = get_radical_characters(self.client, self.dict, radical)
+            .await?
+            .concat();
+        let word = number
+            .checked_sub(1)
+            .and_then(|x| characters.get(x))
+            .ok_or_else(|| anyhow!("Invaild character number: {}", number))?;
+        self.command = MeowdictRunCommand::Show;
+        self.words = Some(vec![word.clone()]);
+        self.input_s2t = false;
+
+        self.match_command_to_run().await
+    }
+
+    /// Fill in suggestions of headwords that were not found, ranking their
+    /// OpenCC variants first
+    async fn with_suggestions(
+        &self,
+        results: Vec<Result<MoedictRawResult>>,
+    ) -> Vec<Result<MoedictRawResult>> {
+        if !results
+            .iter()
+            .any(|x| matches!(x, Err(e) if e.is::<KeywordNotFound>()))
+        {
+            return results;
+        }
+        let moedict_index = match get_moedict_index(self.client, self.dict).await {
+            Ok(moedict_index) => moedict_index,
+            Err(_) => return results,
+        };
+
+        results
+            .into_iter()
+            .map(|x| {
+                x.map_err(|e| match e.downcast::<KeywordNotFound>() {
+                    Ok(mut e) => {
+                        let variants = [OpenccConvertMode::S2T, OpenccConvertMode::T2S]
+                            .map(|x| opencc_convert(&e.keyword, x));
+                        e.suggestions = rank_suggestions(&e.keyword, &variants, &moedict_index);
+                        e.into()
+                    }
+                    Err(e) => e,
+                })
+            })
+            .collect()
+    }
+
+    /// Entries available offline with every term in their definitions,
+    /// quotes or examples
+    fn grep_result(&mut self) -> Result<String> {
+        let terms = self.words()?.to_vec();
+        let hits = grep_entries(
+            self.dict,
+            self.client.cache.entry_ttl,
+            &terms,
+            &self.search.fields,
+        )?;
+        let page = SearchPage::new(hits.len(), self.search.page, self.search.per_page);
+        let hits = &hits[page.range()];
+        let result = gen_grep_str(&terms, hits, &page, self.numbered, get_terminal_size());
+        self.references = hits.iter().map(|x| x.title.clone()).collect();
+
+        Ok(result)
+    }
+
+    fn with_links(&self, result: String, links: &[String], terminal_size: usize) -> String {
+        if !self.numbered || links.is_empty() {
+            return result;
+        }
+
+        format!("{}\n{}", result, gen_links_str(links, terminal_size))
+    }
+
+    /// OSC 8 sequences survive `strip_ansi_codes`, so no hyperlinks without color
+    fn hyperlink_dict(&self) -> Option<MoedictDictionary> {
+        (self.hyperlinks && !self.no_color).then_some(self.dict)
+    }
+
+    fn words(&self) -> Result<&[String]> {
+        self.words
+            .as_deref()
+            .ok_or_else(|| anyhow!("Please enter words!"))
+    }
+
     fn setup_result(&self, result: &str) -> String {
         let result = if self.no_color {
             strip_ansi_codes(result).to_string()
@@ -114,13 +371,20 @@ impl MeowdictResponse<'_> {
             result.to_string()
         };
 
-        if self.result_t2s {
-            opencc_convert(&result, OpenccConvertMode::T2S)
-        } else {
+        if !self.result_t2s {
             result
+        } else if self.is_json_output() {
+            let opencc = OpenCC::new(DefaultConfig::TW2S).unwrap();
+            convert_json_strings(&result, |s| opencc.convert(s))
+        } else {
+            opencc_convert(&result, OpenccConvertMode::T2S)
         }
     }
 
+    fn is_json_output(&self) -> bool {
+        matches!(self.command, MeowdictRunCommand::Json) || self.json
+    }
+
     fn words_input_s2t(&self) -> Option<Vec<String>> {
         self.words.as_ref().map(|words| {
             if self.input_s2t {
@@ -135,7 +399,92 @@ impl MeowdictResponse<'_> {
     }
 }
 
-fn get_terminal_size() -> usize {
+const PREFETCH_CHUNK_SIZE: usize = 16;
+
+pub async fn run_cache_command(
+    client: &MeowdictClient,
+    subcmd: Option<(&str, &ArgMatches)>,
+    dict: MoedictDictionary,
+    no_color: bool,
+) -> Result<()> {
+    let result = match subcmd {
+        Some(("refresh", _)) => {
+            let jyutping_table = refresh_wordshk(client).await?;
+            let moedict_index = refresh_moedict_index(client, dict).await?;
+            format!(
+                "Refreshed {} words.hk entries and {} moedict index entries ({})",
+                jyutping_table.len(),
+                moedict_index.len(),
+                dict
+            )
+        }
+        Some(("clear", _)) => {
+            clear_cache()?;
+            "Cache cleared".to_string()
+        }
+        Some(("prefetch", args)) => {
+            let words_path = args.get_one::<String>("FILE").unwrap();
+            let (ok, total) = prefetch_words(client, Path::new(words_path), dict).await?;
+            format!("Prefetched {}/{} entries ({})", ok, total, dict)
+        }
+        _ => gen_cache_status_str(&cache_status()),
+    };
+    if no_color {
+        println!("{}", strip_ansi_codes(&result));
+    } else {
+        println!("{}", result);
+    }
+
+    Ok(())
+}
+
+/// Warm the entry cache with every whitespace separated word of `path`
+async fn prefetch_words(
+    client: &MeowdictClient,
+    path: &Path,
+    dict: MoedictDictionary,
+) -> Result<(usize, usize)> {
+    let content = fs::read_to_string(path)?;
+    let mut seen = HashSet::new();
+    let words = content
+        .split_whitespace()
+        .filter(|x| seen.insert(*x))
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    let mut ok = 0;
+    for chunk in words.chunks(PREFETCH_CHUNK_SIZE) {
+        ok += get_dict_result(client, chunk, dict)
+            .await
+            .iter()
+            .filter(|x| x.is_ok())
+            .count();
+    }
+
+    Ok((ok, words.len()))
+}
+
+/// Links of the found entries, then suggestions for those not found
+fn collect_references(results: &[Result<MoedictRawResult>]) -> Vec<String> {
+    let mut references = Vec::new();
+    let suggestions = results
+        .iter()
+        .filter_map(|x| x.as_ref().err()?.downcast_ref::<KeywordNotFound>())
+        .flat_map(|x| x.suggestions.clone());
+    for word in results
+        .iter()
+        .flatten()
+        .flat_map(|x| x.references())
+        .chain(suggestions)
+    {
+        if !references.contains(&word) {
+            references.push(word);
+        }
+    }
+
+    references
+}
+
+pub fn get_terminal_size() -> usize {
     Term::stdout().size().1.into()
 }
 
@@ -1,3 +1,5 @@
+use std::{borrow::Cow, ops::Range, time::SystemTime};
+
 use anyhow::Result;
 use console::truncate_str;
 use indexmap::IndexMap;
@@ -5,13 +7,19 @@ use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 
 use crate::api::*;
+use crate::cache::CacheStatus;
+use crate::cantonese::CantoneseRomanization;
+use crate::grep::{GrepField, GrepHit};
 
 const TERMINAL_LINE_LIMIT_LENGTH: usize = 80;
+/// Characters kept on each side of the first hit in a grep snippet
+const SNIPPET_CONTEXT: usize = 24;
 
 macro_rules! push_qel {
     ($qel:expr, $result:ident, $count:ident, $t:ident) => {
         if let Some(qel) = &$qel {
-            qel.into_iter().for_each(|x| $result[$t][$count].push(x))
+            qel.into_iter()
+                .for_each(|x| $result[$t][$count].push(Cow::from(x)))
         }
     };
 }
@@ -28,15 +36,46 @@ pub enum OpenccConvertMode {
     T2S,
 }
 
-pub fn gen_dict_result_str(moedict_result: Vec<MoedictRawResult>, terminal_size: usize) -> String {
+pub fn gen_dict_result_str(
+    words: &[String],
+    moedict_result: Vec<Result<MoedictRawResult>>,
+    terminal_size: usize,
+    hyperlink: Option<MoedictDictionary>,
+) -> String {
     let mut result = Vec::new();
 
-    for i in moedict_result {
-        result.push(
-            format!("{}：", i.title)
-                .fg_rgb::<178, 143, 206>()
-                .to_string(),
-        );
+    for (word, i) in words.iter().zip(moedict_result) {
+        let i = match i {
+            Ok(i) => i,
+            Err(e) => {
+                result.push(gen_error_str(word, &e));
+                if let Some(e) = e.downcast_ref::<KeywordNotFound>() {
+                    if !e.suggestions.is_empty() {
+                        result.push(
+                            format!("  是否要找：{}", e.suggestions.join("、"))
+                                .fg_rgb::<148, 148, 148>()
+                                .to_string(),
+                        );
+                    }
+                }
+                continue;
+            }
+        };
+        result.push(gen_title_str(&i.title, i.stale));
+        if let Some(radical) = i.radical {
+            result.push(
+                format!("  部首：{}", radical)
+                    .fg_rgb::<148, 148, 148>()
+                    .to_string(),
+            );
+        }
+        if let Some(stroke_count) = i.stroke_count {
+            let stroke_count = match i.non_radical_stroke_count {
+                Some(non_radical) => format!("  筆畫：{}（部首外 {}）", stroke_count, non_radical),
+                None => format!("  筆畫：{}", stroke_count),
+            };
+            result.push(stroke_count.fg_rgb::<148, 148, 148>().to_string());
+        }
         if let Some(english) = i.english {
             result.push(
                 string_split_new_line(format!("  英語：{}", english), 2, terminal_size)
@@ -46,13 +85,41 @@ pub fn gen_dict_result_str(moedict_result: Vec<MoedictRawResult>, terminal_size:
         }
         if let Some(heteronyms) = i.heteronyms {
             for j in heteronyms {
-                if let Some(pinyin) = j.pinyin {
+                if let Some(hakka) = j.hakka {
+                    for reading in hakka {
+                        result.push(
+                            format!("  {}：{}", reading.dialect, reading.romanization)
+                                .fg_rgb::<236, 184, 138>()
+                                .to_string(),
+                        );
+                    }
+                } else if let Some(pinyin) = j.pinyin {
+                    let pinyin = format!("  拼音：{}", pinyin)
+                        .fg_rgb::<236, 184, 138>()
+                        .to_string();
+                    result.push(match j.reading_kind {
+                        Some(kind) => format!(
+                            "{} {}",
+                            pinyin,
+                            format!("[{}]", kind.label()).fg_rgb::<230, 180, 80>()
+                        ),
+                        None => pinyin,
+                    });
+                }
+                if let Some(tailo) = j.tailo {
                     result.push(
-                        format!("  拼音：{}", pinyin)
+                        format!("  台羅：{}", tailo)
                             .fg_rgb::<236, 184, 138>()
                             .to_string(),
                     );
                 }
+                if let Some(reading) = j.reading {
+                    result.push(
+                        format!("  類別：{}", reading)
+                            .fg_rgb::<208, 90, 110>()
+                            .to_string(),
+                    );
+                }
                 if let Some(bopomofo) = j.bopomofo {
                     result.push(
                         format!("  注音：{}", bopomofo)
@@ -68,25 +135,51 @@ pub fn gen_dict_result_str(moedict_result: Vec<MoedictRawResult>, terminal_size:
                                 .push(format!("{:>3}：", k).fg_rgb::<168, 216, 165>().to_string());
                         }
                         for (index, value) in v.iter().enumerate() {
-                            let result_str = string_split_new_line(
-                                format!("{:>3}.{}", index + 1, value[0].to_string()),
-                                2,
-                                terminal_size,
+                            let (result_str, links) = link_markup_formatter(&format!(
+                                "{:>3}.{}",
+                                index + 1,
+                                interlinear_formatter(&value[0])
+                            ));
+                            result.push(
+                                link_formatter(&result_str, &links, 2, terminal_size, hyperlink)
+                                    .fg_rgb::<129, 199, 212>()
+                                    .to_string(),
                             );
-                            result.push(result_str.fg_rgb::<129, 199, 212>().to_string());
                             if !value[1..].is_empty() {
                                 for s in &value[1..] {
-                                    let result_str = string_split_new_line(
-                                        format!("    {}", s.to_string()),
-                                        4,
-                                        terminal_size,
+                                    let (result_str, links) = link_markup_formatter(&format!(
+                                        "    {}",
+                                        interlinear_formatter(s)
+                                    ));
+                                    result.push(
+                                        link_formatter(
+                                            &result_str,
+                                            &links,
+                                            4,
+                                            terminal_size,
+                                            hyperlink,
+                                        )
+                                        .fg_rgb::<220, 159, 180>()
+                                        .to_string(),
                                     );
-                                    result.push(result_str.fg_rgb::<220, 159, 180>().to_string());
                                 }
                             }
                         }
                     }
                 }
+                for (label, words) in [("近義", j.synonyms), ("反義", j.antonyms)] {
+                    if let Some(words) = words {
+                        let (result_str, links) = link_markup_formatter(&format!(
+                            "  {}",
+                            word_list_formatter(label, &words)
+                        ));
+                        result.push(
+                            link_formatter(&result_str, &links, 2, terminal_size, hyperlink)
+                                .fg_rgb::<220, 159, 180>()
+                                .to_string(),
+                        );
+                    }
+                }
             }
         }
     }
@@ -94,7 +187,341 @@ pub fn gen_dict_result_str(moedict_result: Vec<MoedictRawResult>, terminal_size:
     result.join("\n")
 }
 
-fn definition_formatter(definitions: &[MoedictDefinition]) -> IndexMap<&str, Vec<Vec<&str>>> {
+/// `近義：`自己~、`本人~`, so the words are linked like the rest of a definition
+fn word_list_formatter(label: &str, words: &[String]) -> String {
+    let words = words
+        .iter()
+        .map(|x| format!("`{}~", x))
+        .collect::<Vec<_>>()
+        .join("、");
+
+    format!("{}：{}", label, words)
+}
+
+/// Numbered linked words or hits, opened in the console with `.go N`
+pub fn gen_links_str(links: &[String], terminal_size: usize) -> String {
+    if links.is_empty() {
+        return "No links in the last result".to_string();
+    }
+    let links = links
+        .iter()
+        .enumerate()
+        .map(|(index, x)| format!("{}.{}", index + 1, x))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    string_split_new_line(format!("連結：{}", links), 2, terminal_size)
+        .fg_rgb::<148, 148, 148>()
+        .to_string()
+}
+
+pub fn gen_segment_str(segments: &[String], terminal_size: usize) -> String {
+    string_split_new_line(format!("分詞：{}", segments.join(" / ")), 2, terminal_size)
+        .fg_rgb::<148, 148, 148>()
+        .to_string()
+}
+
+/// Page `page` (from 1) of `total` hits, `per_page` at a time
+pub struct SearchPage {
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+impl SearchPage {
+    /// Pages past the end are clamped to the last one
+    pub fn new(total: usize, page: usize, per_page: usize) -> Self {
+        let per_page = per_page.max(1);
+        let pages = total.div_ceil(per_page).max(1);
+
+        SearchPage {
+            page: page.clamp(1, pages),
+            per_page,
+            total,
+        }
+    }
+
+    pub fn pages(&self) -> usize {
+        self.total.div_ceil(self.per_page).max(1)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        let start = ((self.page - 1) * self.per_page).min(self.total);
+
+        start..(start + self.per_page).min(self.total)
+    }
+
+    fn footer_str(&self) -> String {
+        format!(
+            "  第 {}/{} 頁，共 {} 筆",
+            self.page,
+            self.pages(),
+            self.total
+        )
+        .fg_rgb::<148, 148, 148>()
+        .to_string()
+    }
+}
+
+/// `numbered` prefixes hits with their number on the page
+pub fn gen_search_str(
+    pattern: &str,
+    hits: &[String],
+    page: &SearchPage,
+    numbered: bool,
+    terminal_size: usize,
+) -> String {
+    let mut result = vec![gen_title_str(pattern, false)];
+    if hits.is_empty() {
+        result.push(
+            "  No matching headwords"
+                .fg_rgb::<208, 90, 110>()
+                .to_string(),
+        );
+    } else {
+        let hits = hits
+            .iter()
+            .enumerate()
+            .map(|(index, x)| match numbered {
+                true => format!("{}.{}", index + 1, x),
+                false => x.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        result.push(
+            string_split_new_line(format!("  {}", hits), 2, terminal_size)
+                .fg_rgb::<129, 199, 212>()
+                .to_string(),
+        );
+    }
+    result.push(page.footer_str());
+
+    result.join("\n")
+}
+
+/// Hits in the colors of `gen_dict_result_str`, their terms in bold.
+/// `numbered` prefixes titles with their number on the page.
+pub fn gen_grep_str(
+    terms: &[String],
+    hits: &[GrepHit],
+    page: &SearchPage,
+    numbered: bool,
+    terminal_size: usize,
+) -> String {
+    let mut result = Vec::new();
+    if hits.is_empty() {
+        result.push(gen_error_str(&terms.join(" "), "No matching entries"));
+    }
+    for (index, hit) in hits.iter().enumerate() {
+        let title = match numbered {
+            true => format!("{}.{}", index + 1, hit.title),
+            false => hit.title.clone(),
+        };
+        result.push(gen_title_str(&title, false));
+        for i in &hit.matches {
+            let (snippet, marks) = grep_snippet(&i.text, terms);
+            let render = |tab: usize| {
+                let spans = marks
+                    .iter()
+                    .map(|x| {
+                        let range = x.start + tab..x.end + tab;
+                        (range, "\x1b[1m".to_string(), "\x1b[22m".to_string())
+                    })
+                    .collect::<Vec<_>>();
+                let text = format!("{}{}", " ".repeat(tab), snippet);
+                string_split_new_line_with_spans(&text, &spans, tab, terminal_size)
+            };
+            result.push(match i.field {
+                GrepField::Def => render(2).fg_rgb::<129, 199, 212>().to_string(),
+                GrepField::Quote | GrepField::Example => {
+                    render(4).fg_rgb::<220, 159, 180>().to_string()
+                }
+            });
+        }
+    }
+    result.push(page.footer_str());
+
+    result.join("\n")
+}
+
+/// Cut `text` around the first term, returning the snippet and the byte
+/// ranges of every term in it
+fn grep_snippet(text: &str, terms: &[String]) -> (String, Vec<Range<usize>>) {
+    let chars = text.chars().collect::<Vec<_>>();
+    let terms = terms
+        .iter()
+        .filter(|x| !x.is_empty())
+        .map(|x| x.chars().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let term_at = |index: usize| {
+        terms
+            .iter()
+            .filter(|x| chars[index..].starts_with(x))
+            .map(|x| x.len())
+            .max()
+    };
+    let first = (0..chars.len())
+        .find(|x| term_at(*x).is_some())
+        .unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_CONTEXT);
+    let end = chars
+        .len()
+        .min(first + term_at(first).unwrap_or(0) + SNIPPET_CONTEXT);
+
+    let mut snippet = String::new();
+    let mut hits = Vec::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut index = start;
+    while index < end {
+        match term_at(index) {
+            Some(length) => {
+                let length = length.min(end - index);
+                let start = snippet.len();
+                snippet.extend(&chars[index..index + length]);
+                hits.push(start..snippet.len());
+                index += length;
+            }
+            None => {
+                snippet.push(chars[index]);
+                index += 1;
+            }
+        }
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    (snippet, hits)
+}
+
+pub fn gen_radicals_str(radicals: &[Vec<String>], terminal_size: usize) -> String {
+    let mut result = vec![gen_title_str("部首", false)];
+    result.extend(stroke_groups_formatter(radicals, None, terminal_size));
+
+    result.join("\n")
+}
+
+/// `numbered` prefixes characters with their number across all stroke counts
+pub fn gen_radical_characters_str(
+    radical: &str,
+    characters: &[Vec<String>],
+    numbered: bool,
+    terminal_size: usize,
+) -> String {
+    let mut count = 0;
+    let mut result = vec![gen_title_str(radical, false)];
+    result.extend(stroke_groups_formatter(
+        characters,
+        numbered.then_some(&mut count),
+        terminal_size,
+    ));
+
+    result.join("\n")
+}
+
+fn stroke_groups_formatter(
+    groups: &[Vec<String>],
+    mut count: Option<&mut usize>,
+    terminal_size: usize,
+) -> Vec<String> {
+    groups
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !x.is_empty())
+        .map(|(strokes, words)| {
+            let words = words
+                .iter()
+                .map(|x| match count.as_deref_mut() {
+                    Some(count) => {
+                        *count += 1;
+                        format!("{}.{}", count, x)
+                    }
+                    None => x.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            string_split_new_line(format!("  {} 畫：{}", strokes, words), 4, terminal_size)
+                .fg_rgb::<129, 199, 212>()
+                .to_string()
+        })
+        .collect()
+}
+
+/// Strip moedict's `` `字~ `` markup, returning the text and the byte ranges
+/// of its linked words
+fn link_markup_formatter(s: &str) -> (String, Vec<Range<usize>>) {
+    let mut result = String::new();
+    let mut links = Vec::new();
+    let mut start = None;
+    for c in s.chars() {
+        match c {
+            '`' => start = Some(result.len()),
+            '~' => {
+                if let Some(start) = start.take() {
+                    links.push(start..result.len());
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    if let Some(start) = start {
+        links.push(start..result.len());
+    }
+
+    (result, links)
+}
+
+/// Wrap `s` and underline its `links` and, given a dictionary, wrap them in
+/// OSC 8 hyperlinks to its website
+fn link_formatter(
+    s: &str,
+    links: &[Range<usize>],
+    tab: usize,
+    terminal_size: usize,
+    hyperlink: Option<MoedictDictionary>,
+) -> String {
+    let spans = links
+        .iter()
+        .map(|x| {
+            let word = s[x.clone()]
+                .chars()
+                .filter(|x| !x.is_whitespace())
+                .collect::<String>();
+            match hyperlink.map(|dict| dict.web_url(&word)) {
+                Some(url) => (
+                    x.clone(),
+                    format!("\x1b]8;;{}\x1b\\\x1b[4m", url),
+                    "\x1b[24m\x1b]8;;\x1b\\".to_string(),
+                ),
+                None => (x.clone(), "\x1b[4m".to_string(), "\x1b[24m".to_string()),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    string_split_new_line_with_spans(s, &spans, tab, terminal_size)
+}
+
+/// Taiwanese examples annotate text as `\u{FFF9}漢字\u{FFFA}Tâi-lô\u{FFFB}華語`
+fn interlinear_formatter(s: &str) -> String {
+    if !s.contains('\u{FFF9}') {
+        return s.to_string();
+    }
+
+    s.replace('\u{FFF9}', "")
+        .replace('\u{FFFA}', " ")
+        .replace('\u{FFFB}', "\n")
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+fn definition_formatter(
+    definitions: &[MoedictDefinition],
+) -> IndexMap<&str, Vec<Vec<Cow<'_, str>>>> {
     let mut result = IndexMap::new();
     let mut count: usize = 0;
     for i in definitions {
@@ -110,25 +537,37 @@ fn definition_formatter(definitions: &[MoedictDefinition]) -> IndexMap<&str, Vec
             result[t].push(Vec::new());
         }
         if let Some(f) = &i.def {
-            result[t][count].push(f.as_str());
+            result[t][count].push(Cow::from(f));
         }
         push_qel!(i.quote, result, count, t);
         push_qel!(i.example, result, count, t);
         push_qel!(i.link, result, count, t);
+        if let Some(synonyms) = &i.synonyms {
+            result[t][count].push(Cow::from(word_list_formatter("近義", synonyms)));
+        }
+        if let Some(antonyms) = &i.antonyms {
+            result[t][count].push(Cow::from(word_list_formatter("反義", antonyms)));
+        }
         count += 1;
     }
 
     result
 }
 
-pub fn gen_translation_str(meowdict_results: Vec<MoedictRawResult>) -> String {
+pub fn gen_translation_str(
+    words: &[String],
+    meowdict_results: Vec<Result<MoedictRawResult>>,
+) -> String {
     let mut result = Vec::new();
-    for i in meowdict_results {
-        result.push(
-            format!("{}：", i.title)
-                .fg_rgb::<178, 143, 206>()
-                .to_string(),
-        );
+    for (word, i) in words.iter().zip(meowdict_results) {
+        let i = match i {
+            Ok(i) => i,
+            Err(e) => {
+                result.push(gen_error_str(word, &e));
+                continue;
+            }
+        };
+        result.push(gen_title_str(&i.title, i.stale));
         if let Some(translation) = i.translation {
             for (k, v) in translation {
                 result.push(format!("{}:", k).fg_rgb::<168, 216, 165>().to_string());
@@ -142,69 +581,323 @@ pub fn gen_translation_str(meowdict_results: Vec<MoedictRawResult>) -> String {
     result.join("\n")
 }
 
-pub fn gen_jyutping_str(jyutping_result: Vec<MeowdictJyutPingResult>) -> String {
+pub fn gen_jyutping_str(
+    words: &[String],
+    jyutping_result: Vec<Result<MeowdictJyutPingResult>>,
+    romanization: CantoneseRomanization,
+) -> String {
     let mut result = Vec::new();
-    for i in jyutping_result {
+    for (word, i) in words.iter().zip(jyutping_result) {
+        let i = match i {
+            Ok(i) => i.romanize(romanization),
+            Err(e) => {
+                result.push(gen_error_str(word, &e));
+                continue;
+            }
+        };
         result.push(
             format!("{}：", i.word)
                 .fg_rgb::<178, 143, 206>()
                 .to_string(),
         );
-        result.push(i.jyutping.join("\n").fg_rgb::<168, 216, 165>().to_string());
+        if let Some(segments) = &i.segments {
+            result.push(jyutping_segments_formatter(segments));
+            continue;
+        }
+        let is_ambiguous = i.jyutping.len() > 1;
+        result.extend(
+            i.jyutping
+                .iter()
+                .map(|x| jyutping_reading_formatter(x, is_ambiguous)),
+        );
+    }
+
+    result.join("\n")
+}
+
+/// Composed reading, ambiguous segments marked with `*` and followed by their
+/// other readings
+fn jyutping_segments_formatter(segments: &[JyutPingSegment]) -> String {
+    let composed = segments
+        .iter()
+        .map(|x| match x.jyutping.first() {
+            Some(jyutping) if x.ambiguous => format!("{}*", jyutping)
+                .fg_rgb::<230, 180, 80>()
+                .to_string(),
+            Some(jyutping) => jyutping.fg_rgb::<168, 216, 165>().to_string(),
+            None => x.text.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut result = vec![
+        composed,
+        format!(
+            "composed from {}",
+            segments
+                .iter()
+                .map(|x| x.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        )
+        .fg_rgb::<148, 148, 148>()
+        .to_string(),
+    ];
+    for segment in segments.iter().filter(|x| x.ambiguous) {
+        result.push(
+            format!(
+                "* {} also {}",
+                segment.text,
+                segment.jyutping[1..].join(", ")
+            )
+            .fg_rgb::<148, 148, 148>()
+            .to_string(),
+        );
     }
 
     result.join("\n")
 }
 
+/// `haang4 75.0% (primary)`, usage details only shown when they are known
+fn jyutping_reading_formatter(reading: &JyutPingReading, is_ambiguous: bool) -> String {
+    let mut details = Vec::new();
+    if let Some(share) = reading.share {
+        details.push(format!("{:.1}%", share * 100.0));
+    }
+    if reading.primary && is_ambiguous {
+        details.push("(primary)".to_string());
+    }
+    let jyutping = reading.jyutping.fg_rgb::<168, 216, 165>().to_string();
+
+    if details.is_empty() {
+        jyutping
+    } else {
+        format!(
+            "{} {}",
+            jyutping,
+            details.join(" ").fg_rgb::<148, 148, 148>()
+        )
+    }
+}
+
 pub fn gen_dict_json_str(moedict_results: Vec<MeowdictJsonResult>) -> Result<String> {
     Ok(serde_json::to_string(&moedict_results)?)
 }
 
+/// `numbered` prefixes hits with their number across all descriptions
 pub fn gen_wantwords_str(
-    words: &[String],
-    wantwords_results: Vec<Vec<WantWordsResult>>,
+    wantwords_results: Vec<MeowdictWantWordsResult>,
     is_all: bool,
-) -> Vec<String> {
+    numbered: bool,
+) -> String {
     let mut result = Vec::new();
-    for (index, wantwords_result) in wantwords_results.into_iter().enumerate() {
-        let mut s = vec![format!("{}：", words[index])];
-        for wantwords_result_item in wantwords_result {
-            if !is_all && wantwords_result_item.correlation == "00" {
-                continue;
-            }
-            s.push(format!(
-                "{}: {}",
-                wantwords_result_item.word, wantwords_result_item.correlation
+    let mut count = 0;
+    for i in wantwords_formatter(wantwords_results, is_all) {
+        if let Some(error) = i.error {
+            result.push(gen_error_str(&i.description, &error));
+            continue;
+        }
+        result.push(gen_title_str(&i.description, i.stale));
+        for j in i.words {
+            let number = if numbered {
+                count += 1;
+                format!("{}.", count)
+            } else {
+                String::new()
+            };
+            result.push(format!(
+                "{}{}: {}",
+                number,
+                j.word.fg_rgb::<168, 216, 165>(),
+                j.correlation.fg_rgb::<220, 159, 180>()
             ));
         }
-        result.push(s.join("\n"));
+    }
+
+    result.join("\n")
+}
+
+pub fn gen_wantwords_json_str(
+    wantwords_results: Vec<MeowdictWantWordsResult>,
+    is_all: bool,
+) -> Result<String> {
+    Ok(serde_json::to_string(&wantwords_formatter(
+        wantwords_results,
+        is_all,
+    ))?)
+}
+
+fn wantwords_formatter(
+    wantwords_results: Vec<MeowdictWantWordsResult>,
+    is_all: bool,
+) -> Vec<MeowdictWantWordsResult> {
+    wantwords_results
+        .into_iter()
+        .map(|mut x| {
+            x.words.retain(|x| is_all || !x.is_zero_correlation());
+            x
+        })
+        .collect()
+}
+
+pub fn gen_cache_status_str(status: &[CacheStatus]) -> String {
+    status
+        .iter()
+        .map(|x| {
+            let detail = match x.modified {
+                Some(modified) => format!(
+                    "{} file(s), {}, updated {}",
+                    x.files,
+                    human_size(x.size),
+                    human_age(modified)
+                ),
+                None => "empty".to_string(),
+            };
+
+            format!(
+                "{}\n  {}\n  {}",
+                gen_title_str(&x.name, false),
+                detail,
+                x.path.display().fg_rgb::<148, 148, 148>()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn human_size(size: u64) -> String {
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut size = size as f64 / 1024.0;
+    for unit in ["KiB", "MiB"] {
+        if size < 1024.0 {
+            return format!("{:.1} {}", size, unit);
+        }
+        size /= 1024.0;
+    }
+
+    format!("{:.1} GiB", size)
+}
+
+fn human_age(modified: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(modified)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} d ago", secs / 86400),
+    }
+}
+
+fn gen_title_str(title: &str, stale: bool) -> String {
+    let title = format!("{}：", title).fg_rgb::<178, 143, 206>().to_string();
+    if stale {
+        format!("{} {}", title, "[stale]".fg_rgb::<208, 90, 110>())
+    } else {
+        title
+    }
+}
+
+fn gen_error_str<E: std::fmt::Display + ?Sized>(word: &str, error: &E) -> String {
+    format!(
+        "{}\n{}",
+        format!("{}：", word).fg_rgb::<178, 143, 206>(),
+        format!("  {}", error).fg_rgb::<208, 90, 110>()
+    )
+}
+
+/// Apply `convert` to every string value of a serialized JSON document,
+/// leaving object keys and escaping intact
+pub fn convert_json_strings<F: Fn(&str) -> String>(json: &str, convert: F) -> String {
+    let mut result = String::with_capacity(json.len());
+    let mut chars = json.char_indices();
+    while let Some((start, c)) = chars.next() {
+        if c != '"' {
+            result.push(c);
+            continue;
+        }
+        let mut end = json.len();
+        let mut escaped = false;
+        for (index, c) in chars.by_ref() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                end = index + 1;
+                break;
+            }
+        }
+        let literal = &json[start..end];
+        let is_key = json[end..].trim_start().starts_with(':');
+        match serde_json::from_str::<String>(literal) {
+            Ok(s) if !is_key => {
+                result.push_str(&serde_json::Value::String(convert(&s)).to_string())
+            }
+            _ => result.push_str(literal),
+        }
     }
 
     result
 }
 
 fn string_split_new_line(s: String, tab: usize, terminal_size: usize) -> String {
-    let mut result_str = String::new();
+    string_split_new_line_with_spans(&s, &[], tab, terminal_size)
+}
+
+/// Split like `string_split_new_line`, putting each span's open and close
+/// codes around its byte range on every line it covers, so a span broken
+/// over lines leaves the indentation alone
+fn string_split_new_line_with_spans(
+    s: &str,
+    spans: &[(Range<usize>, String, String)],
+    tab: usize,
+    terminal_size: usize,
+) -> String {
     let limit_length = if terminal_size < TERMINAL_LINE_LIMIT_LENGTH {
         terminal_size
     } else {
         TERMINAL_LINE_LIMIT_LENGTH
     };
-    let mut ref_s = s.as_str();
-    let mut i = 0;
-    let tab_string = " ".repeat(tab);
-    let tail = format!("\n{}", tab_string);
+    let tail = format!("\n{}", " ".repeat(tab));
+    let mut lines = Vec::new();
+    let mut start = 0;
     loop {
-        let truncate_string = truncate_str(ref_s, limit_length, &tail).to_string();
-        result_str.push_str(&truncate_string);
-        if s.len() == result_str.len() - i * tab - i {
+        let truncate_string = truncate_str(&s[start..], limit_length, &tail);
+        if start + truncate_string.len() == s.len() {
+            lines.push(start..s.len());
             break;
         }
-        ref_s = &ref_s[truncate_string.len() - tab - 1..];
-        i += 1;
+        let end = start + truncate_string.len() - tab - 1;
+        lines.push(start..end);
+        start = end;
     }
 
-    result_str
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut result = String::new();
+            let mut index = line.start;
+            for (range, open, close) in spans {
+                let start = range.start.max(line.start);
+                let end = range.end.min(line.end);
+                if start >= end {
+                    continue;
+                }
+                result.push_str(&s[index..start]);
+                result.push_str(open);
+                result.push_str(&s[start..end]);
+                result.push_str(close);
+                index = end;
+            }
+            result.push_str(&s[index..line.end]);
+            result
+        })
+        .collect::<Vec<_>>()
+        .join(&tail)
 }
 
 #[test]
@@ -212,9 +905,12 @@ fn test_result_str() {
     use console::strip_ansi_codes;
     let test_str = r#"{"t":"空穴來風","translation":{"English":["lit. wind from an empty cave (idiom)","fig. unfounded (story)","baseless (claim)"],"francais":["(expr. idiom.) les fissures laissent passer le vent","les faiblesses donnent prise à la médisance","prêter le flanc à la critique"]},"h":[{"p":"kōng xuè lái fēng","b":"ㄎㄨㄥ　ㄒㄩㄝˋ　ㄌㄞˊ　ㄈㄥ","d":[{"type":null,"q":null,"e":null,"f":"有空穴，就有風吹來。語出《文選．宋玉．風賦》：「臣聞於師：『枳句來巢，空穴來風，其所託者然，則風氣殊焉。』」後比喻流言乘隙而入。如：「那些空穴來風的傳聞，不足以採信。」","l":null}]}],"English":"lit. wind from an empty cave (idiom)"}"#;
     let test_obj: MoedictRawResult = serde_json::from_str(test_str).unwrap();
+    let words = vec!["空穴來風".to_string()];
     let result_with_less_80 = strip_ansi_codes(&gen_dict_result_str(
-        vec![test_obj.clone()],
+        &words,
+        vec![Ok(test_obj.clone())],
         TERMINAL_LINE_LIMIT_LENGTH - 1,
+        None,
     ))
     .to_string();
     let right_result_with_less_80 = r#"空穴來風：
@@ -225,8 +921,10 @@ fn test_result_str() {
   穴來風，其所託者然，則風氣殊焉。』」後比喻流言乘隙而入。如：「那些空穴來風的
   傳聞，不足以採信。」"#;
     let result_with_more_80 = strip_ansi_codes(&gen_dict_result_str(
-        vec![test_obj],
+        &words,
+        vec![Ok(test_obj)],
         TERMINAL_LINE_LIMIT_LENGTH + 1,
+        None,
     ))
     .to_string();
     let right_result_with_more_80 = r#"空穴來風：
@@ -241,12 +939,183 @@ fn test_result_str() {
     assert_eq!(result_with_more_80, right_result_with_more_80);
 }
 
+#[test]
+fn test_result_str_not_found() {
+    use anyhow::anyhow;
+    use console::strip_ansi_codes;
+    let test_str = r#"{"t":"我","h":[{"p":"wǒ","b":"ㄨㄛˇ","d":[{"f":"自稱。"}]}]}"#;
+    let test_obj: MoedictRawResult = serde_json::from_str(test_str).unwrap();
+    let words = vec!["我".to_string(), "不存在的詞".to_string(), "我".to_string()];
+    let result_str = strip_ansi_codes(&gen_dict_result_str(
+        &words,
+        vec![
+            Ok(test_obj.clone()),
+            Err(anyhow!("Could not find keyword: 不存在的詞")),
+            Ok(test_obj),
+        ],
+        TERMINAL_LINE_LIMIT_LENGTH,
+        None,
+    ))
+    .to_string();
+    let right_str = r#"我：
+  拼音：wǒ
+  注音：ㄨㄛˇ
+  1.自稱。
+不存在的詞：
+  Could not find keyword: 不存在的詞
+我：
+  拼音：wǒ
+  注音：ㄨㄛˇ
+  1.自稱。"#;
+
+    assert_eq!(result_str, right_str);
+}
+
+#[test]
+fn test_suggestions_str() {
+    use console::strip_ansi_codes;
+    let words = vec!["不存在的詞".to_string()];
+    let not_found = |suggestions: Vec<String>| {
+        let result = vec![Err(KeywordNotFound {
+            keyword: "不存在的詞".to_string(),
+            suggestions,
+        }
+        .into())];
+        strip_ansi_codes(&gen_dict_result_str(
+            &words,
+            result,
+            TERMINAL_LINE_LIMIT_LENGTH,
+            None,
+        ))
+        .to_string()
+    };
+    let right_str = r#"不存在的詞：
+  Could not find keyword: 不存在的詞
+  是否要找：存在、不在"#;
+
+    assert_eq!(
+        not_found(vec!["存在".to_string(), "不在".to_string()]),
+        right_str
+    );
+    assert_eq!(
+        not_found(Vec::new()),
+        "不存在的詞：\n  Could not find keyword: 不存在的詞"
+    );
+}
+
+#[test]
+fn test_reading_kind_str() {
+    use console::strip_ansi_codes;
+    let test_str = r#"{"t":"我","h":[{"p":"wǒ","reading_kind":"colloquial","d":[{"f":"自稱。"}]},{"p":"ě","reading_kind":"literary","d":[{"f":"(一)之讀音。"}]}]}"#;
+    let test_obj: MoedictRawResult = serde_json::from_str(test_str).unwrap();
+    let words = vec!["我".to_string()];
+    let result_str = strip_ansi_codes(&gen_dict_result_str(
+        &words,
+        vec![Ok(test_obj)],
+        TERMINAL_LINE_LIMIT_LENGTH,
+        None,
+    ))
+    .to_string();
+    let right_str = r#"我：
+  拼音：wǒ [語音]
+  1.自稱。
+  拼音：ě [讀音]
+  1.(一)之讀音。"#;
+
+    assert_eq!(result_str, right_str);
+}
+
+#[test]
+fn test_dialect_result_str() {
+    use console::strip_ansi_codes;
+    let test_obj = MoedictRawResult {
+        title: "我".to_string(),
+        translation: None,
+        heteronyms: Some(vec![MoedictHeteronym {
+            pinyin: Some("四⃞ngâi 海⃞ngài".to_string()),
+            bopomofo: None,
+            reading_kind: None,
+            definitions: Some(vec![MoedictDefinition {
+                word_type: None,
+                quote: None,
+                example: Some(vec![
+                    "\u{FFF9}我食飽矣。\u{FFFA}Guá tsia̍h pá ah.\u{FFFB}我吃飽了。".to_string(),
+                ]),
+                def: Some("自稱。".to_string()),
+                link: None,
+                synonyms: None,
+                antonyms: None,
+                references: Vec::new(),
+            }]),
+            tailo: Some("guá".to_string()),
+            reading: Some("文".to_string()),
+            hakka: Some(vec![
+                HakkaReading {
+                    dialect: "四縣".to_string(),
+                    romanization: "ngâi".to_string(),
+                },
+                HakkaReading {
+                    dialect: "海陸".to_string(),
+                    romanization: "ngài".to_string(),
+                },
+            ]),
+            synonyms: None,
+            antonyms: None,
+        }]),
+        english: None,
+        radical: None,
+        stroke_count: None,
+        non_radical_stroke_count: None,
+        stale: false,
+    };
+    let words = vec!["我".to_string()];
+    let result_str = strip_ansi_codes(&gen_dict_result_str(
+        &words,
+        vec![Ok(test_obj)],
+        TERMINAL_LINE_LIMIT_LENGTH,
+        None,
+    ))
+    .to_string();
+    let right_str = r#"我：
+  四縣：ngâi
+  海陸：ngài
+  台羅：guá
+  類別：文
+  1.自稱。
+    我食飽矣。 Guá tsia̍h pá ah. / 我吃飽了。"#;
+
+    assert_eq!(result_str, right_str);
+}
+
+#[test]
+fn test_stale_result_str() {
+    use console::strip_ansi_codes;
+    let mut test_obj: MoedictRawResult =
+        serde_json::from_str(r#"{"t":"我","h":[{"d":[{"f":"自稱。"}]}]}"#).unwrap();
+    test_obj.stale = true;
+    let words = vec!["我".to_string()];
+    let result_str = strip_ansi_codes(&gen_dict_result_str(
+        &words,
+        vec![Ok(test_obj)],
+        TERMINAL_LINE_LIMIT_LENGTH,
+        None,
+    ))
+    .to_string();
+    let right_str = r#"我： [stale]
+  1.自稱。"#;
+
+    assert_eq!(result_str, right_str);
+    assert_eq!(strip_ansi_codes(&gen_title_str("我", false)), "我：");
+    assert_eq!(strip_ansi_codes(&gen_title_str("我", true)), "我： [stale]");
+}
+
 #[test]
 fn test_transtation_str() {
     use console::strip_ansi_codes;
     let test_str = r#"{"t":"空穴來風","translation":{"English":["lit. wind from an empty cave (idiom)","fig. unfounded (story)","baseless (claim)"],"francais":["(expr. idiom.) les fissures laissent passer le vent","les faiblesses donnent prise à la médisance","prêter le flanc à la critique"]},"h":[{"p":"kōng xuè lái fēng","b":"ㄎㄨㄥ　ㄒㄩㄝˋ　ㄌㄞˊ　ㄈㄥ","d":[{"type":null,"q":null,"e":null,"f":"有空穴，就有風吹來。語出《文選．宋玉．風賦》：「臣聞於師：『枳句來巢，空穴來風，其所託者然，則風氣殊焉。』」後比喻流言乘隙而入。如：「那些空穴來風的傳聞，不足以採信。」","l":null}]}],"English":"lit. wind from an empty cave (idiom)"}"#;
     let test_obj: MoedictRawResult = serde_json::from_str(test_str).unwrap();
-    let result_str = strip_ansi_codes(&gen_translation_str(vec![test_obj])).to_string();
+    let words = vec!["空穴來風".to_string()];
+    let result_str = strip_ansi_codes(&gen_translation_str(&words, vec![Ok(test_obj)])).to_string();
     let right_str = r#"空穴來風：
 English:
 lit. wind from an empty cave (idiom)
@@ -265,11 +1134,315 @@ fn test_jyutping_str() {
     use console::strip_ansi_codes;
     let test_obj = MeowdictJyutPingResult {
         word: "我".to_string(),
-        jyutping: vec!["ngo5".to_string()],
+        jyutping: vec![JyutPingReading::new("ngo5", None, None, false)],
+        segments: None,
+    };
+    let ranked_obj = MeowdictJyutPingResult {
+        word: "行".to_string(),
+        jyutping: vec![
+            JyutPingReading::new("haang4", None, Some(0.75), true),
+            JyutPingReading::new("hang4", None, Some(0.25), false),
+        ],
+        segments: None,
+    };
+    let composed_obj = MeowdictJyutPingResult {
+        word: "行路。".to_string(),
+        jyutping: vec![JyutPingReading::new("haang4 lou6 。", None, None, false)],
+        segments: Some(vec![
+            JyutPingSegment::new("行", &["haang4", "hang4"]),
+            JyutPingSegment::new("路", &["lou6"]),
+            JyutPingSegment::new("。", &[]),
+        ]),
     };
-    let result_str = strip_ansi_codes(&gen_jyutping_str(vec![test_obj])).to_string();
+    let words = vec!["我".to_string(), "行".to_string(), "行路。".to_string()];
+    let yale_str = strip_ansi_codes(&gen_jyutping_str(
+        &words[..1],
+        vec![Ok(test_obj.clone())],
+        CantoneseRomanization::Yale,
+    ))
+    .to_string();
+    let result_str = strip_ansi_codes(&gen_jyutping_str(
+        &words,
+        vec![Ok(test_obj), Ok(ranked_obj), Ok(composed_obj)],
+        CantoneseRomanization::Jyutping,
+    ))
+    .to_string();
     let right_str = r#"我：
-ngo5"#;
+ngo5
+行：
+haang4 75.0% (primary)
+hang4 25.0%
+行路。：
+haang4* lou6 。
+composed from 行 | 路 | 。
+* 行 also hang4"#;
 
     assert_eq!(result_str, right_str);
+    assert_eq!(yale_str, "我：\nngóh");
+}
+
+#[test]
+fn test_wantwords_str() {
+    use console::strip_ansi_codes;
+    let wantwords_results = || {
+        vec![MeowdictWantWordsResult {
+            description: "天上的星星".to_string(),
+            words: vec![
+                WantWordsResult {
+                    correlation: "96".to_string(),
+                    word: "星辰".to_string(),
+                },
+                WantWordsResult {
+                    correlation: "00".to_string(),
+                    word: "星斗".to_string(),
+                },
+            ],
+            error: None,
+            stale: false,
+        }]
+    };
+    let result_str =
+        strip_ansi_codes(&gen_wantwords_str(wantwords_results(), false, false)).to_string();
+    let right_str = r#"天上的星星：
+星辰: 96"#;
+    let result_all_str =
+        strip_ansi_codes(&gen_wantwords_str(wantwords_results(), true, false)).to_string();
+    let right_all_str = r#"天上的星星：
+星辰: 96
+星斗: 00"#;
+    let result_numbered_str =
+        strip_ansi_codes(&gen_wantwords_str(wantwords_results(), true, true)).to_string();
+    let right_numbered_str = r#"天上的星星：
+1.星辰: 96
+2.星斗: 00"#;
+    let result_json_str = gen_wantwords_json_str(wantwords_results(), false).unwrap();
+    let right_json_str = r#"[{"description":"天上的星星","words":[{"correlation":"96","word":"星辰"}],"error":null}]"#;
+
+    assert_eq!(result_str, right_str);
+    assert_eq!(result_all_str, right_all_str);
+    assert_eq!(result_numbered_str, right_numbered_str);
+    assert_eq!(result_json_str, right_json_str);
+}
+
+#[test]
+fn test_convert_json_strings() {
+    let json = r#"{"name":"我","translation":{"English":["say \"hi\"","a\\b"]},"jyutping":null}"#;
+    let result = convert_json_strings(json, |s| s.to_uppercase());
+    let right_result =
+        r#"{"name":"我","translation":{"English":["SAY \"HI\"","A\\B"]},"jyutping":null}"#;
+
+    assert_eq!(result, right_result);
+}
+
+#[test]
+fn test_cache_status_str() {
+    use std::{path::PathBuf, time::Duration};
+    let status = vec![
+        CacheStatus {
+            name: "words.hk".to_string(),
+            path: PathBuf::from("/tmp/jyutping.json"),
+            files: 1,
+            size: 1536,
+            modified: Some(SystemTime::now() - Duration::from_secs(7200)),
+        },
+        CacheStatus {
+            name: "WantWords entries".to_string(),
+            path: PathBuf::from("/tmp/meowdict/wantwords"),
+            files: 0,
+            size: 0,
+            modified: None,
+        },
+    ];
+
+    assert_eq!(
+        console::strip_ansi_codes(&gen_cache_status_str(&status)),
+        "words.hk：\n  1 file(s), 1.5 KiB, updated 2 h ago\n  /tmp/jyutping.json\nWantWords entries：\n  empty\n  /tmp/meowdict/wantwords"
+    );
+    assert_eq!(human_size(512), "512 B");
+}
+
+#[test]
+fn test_link_str() {
+    use console::strip_ansi_codes;
+    let definition = "有空穴，就有風吹來。語出《文選．宋玉．風賦》：「臣聞於師：『枳句來巢，空穴來風，其所託者然，則風氣殊焉。』」";
+    let marked = definition
+        .replace("空穴來風", "`空穴來風~")
+        .replace("文選", "`文選~");
+    let entry = |def: &str| {
+        serde_json::from_value::<MoedictRawResult>(serde_json::json!({
+            "t": "空穴來風",
+            "h": [{"p": "kōng xuè lái fēng", "d": [{"f": def}]}]
+        }))
+        .unwrap()
+    };
+    let words = vec!["空穴來風".to_string()];
+    let render = |def: &str, width, hyperlink| {
+        gen_dict_result_str(&words, vec![Ok(entry(def))], width, hyperlink)
+    };
+    let underlined = render(&marked, TERMINAL_LINE_LIMIT_LENGTH, None);
+    let hyperlinked = render(&marked, 40, Some(MoedictDictionary::Mandarin));
+
+    assert!(underlined.contains("\x1b[4m文選\x1b[24m"));
+    assert_eq!(
+        strip_ansi_codes(&underlined),
+        strip_ansi_codes(&render(definition, TERMINAL_LINE_LIMIT_LENGTH, None))
+    );
+    // The link wraps onto the next line, so it is closed before the break
+    assert!(hyperlinked.contains(
+        "\x1b]8;;https://www.moedict.tw/%E7%A9%BA%E7%A9%B4%E4%BE%86%E9%A2%A8\x1b\\\x1b[4m"
+    ));
+    assert!(hyperlinked.contains(
+        "\x1b[24m\x1b]8;;\x1b\\\n  \x1b]8;;https://www.moedict.tw/%E7%A9%BA%E7%A9%B4%E4%BE%86%E9%A2%A8\x1b\\\x1b[4m穴來風"
+    ));
+    // Zero width joiners in the text are left alone
+    assert!(render("`家~\u{200D}人", TERMINAL_LINE_LIMIT_LENGTH, None)
+        .contains("\x1b[4m家\x1b[24m\u{200D}人"));
+    assert_eq!(
+        strip_ansi_codes(&gen_links_str(
+            &["自".to_string(), "稱".to_string()],
+            TERMINAL_LINE_LIMIT_LENGTH
+        )),
+        "連結：1.自  2.稱"
+    );
+}
+
+#[test]
+fn test_character_result_str() {
+    use console::strip_ansi_codes;
+    let test_str = r#"{"t":"我","r":"戈","c":7,"n":3,"h":[{"p":"wǒ","d":[{"f":"自稱。","s":"自己,本人","a":"你"}],"s":["吾"]}]}"#;
+    let test_obj: MoedictRawResult = serde_json::from_str(test_str).unwrap();
+    let result_str = gen_dict_result_str(
+        &["我".to_string()],
+        vec![Ok(test_obj)],
+        TERMINAL_LINE_LIMIT_LENGTH,
+        None,
+    );
+    let right_str = r#"我：
+  部首：戈
+  筆畫：7（部首外 3）
+  拼音：wǒ
+  1.自稱。
+    近義：自己、本人
+    反義：你
+  近義：吾"#;
+
+    assert_eq!(strip_ansi_codes(&result_str), right_str);
+    assert!(result_str.contains("\x1b[4m自己\x1b[24m"));
+}
+
+#[test]
+fn test_radical_str() {
+    use console::strip_ansi_codes;
+    let radicals = vec![
+        vec![],
+        vec!["一".to_string(), "丨".to_string()],
+        vec![],
+        vec!["口".to_string()],
+    ];
+    let characters = vec![
+        vec!["戈".to_string()],
+        vec!["戊".to_string(), "戉".to_string()],
+        vec![],
+        vec!["我".to_string()],
+    ];
+
+    assert_eq!(
+        strip_ansi_codes(&gen_radicals_str(&radicals, TERMINAL_LINE_LIMIT_LENGTH)),
+        "部首：\n  1 畫：一 丨\n  3 畫：口"
+    );
+    assert_eq!(
+        strip_ansi_codes(&gen_radical_characters_str(
+            "戈",
+            &characters,
+            true,
+            TERMINAL_LINE_LIMIT_LENGTH
+        )),
+        "戈：\n  0 畫：1.戈\n  1 畫：2.戊 3.戉\n  3 畫：4.我"
+    );
+}
+
+#[test]
+fn test_segment_str() {
+    let segments = ["今天", "天氣", "很好"].map(|x| x.to_string());
+
+    assert_eq!(
+        console::strip_ansi_codes(&gen_segment_str(&segments, TERMINAL_LINE_LIMIT_LENGTH)),
+        "分詞：今天 / 天氣 / 很好"
+    );
+}
+
+#[test]
+fn test_search_str() {
+    use console::strip_ansi_codes;
+    let hits = ["空穴來風", "穴來風", "來風"].map(|x| x.to_string());
+    let page = SearchPage::new(hits.len(), 2, 2);
+
+    assert_eq!(page.range(), 2..3);
+    assert_eq!(SearchPage::new(3, 9, 2).page, 2);
+    assert_eq!(SearchPage::new(0, 1, 2).range(), 0..0);
+    assert_eq!(
+        strip_ansi_codes(&gen_search_str(
+            "*來風",
+            &hits[..2],
+            &SearchPage::new(hits.len(), 1, 2),
+            true,
+            TERMINAL_LINE_LIMIT_LENGTH
+        )),
+        "*來風：\n  1.空穴來風  2.穴來風\n  第 1/2 頁，共 3 筆"
+    );
+    assert_eq!(
+        strip_ansi_codes(&gen_search_str(
+            "^一$",
+            &[],
+            &SearchPage::new(0, 1, 2),
+            false,
+            TERMINAL_LINE_LIMIT_LENGTH
+        )),
+        "^一$：\n  No matching headwords\n  第 1/1 頁，共 0 筆"
+    );
+}
+
+#[test]
+fn test_grep_str() {
+    use crate::grep::GrepMatch;
+    use console::strip_ansi_codes;
+    let terms = vec!["風".to_string()];
+    let hits = vec![GrepHit {
+        title: "空穴來風".to_string(),
+        matches: vec![
+            GrepMatch {
+                field: GrepField::Def,
+                text: "有空穴，就有風\u{2060}吹來。".to_string(),
+            },
+            GrepMatch {
+                field: GrepField::Quote,
+                text: format!("{}風賦", "宋".repeat(30)),
+            },
+        ],
+        score: 4,
+    }];
+    let result_str = gen_grep_str(
+        &terms,
+        &hits,
+        &SearchPage::new(1, 1, 50),
+        true,
+        TERMINAL_LINE_LIMIT_LENGTH,
+    );
+    let right_str = format!(
+        "1.空穴來風：\n  有空穴，就有風\u{2060}吹來。\n    …{}風賦\n  第 1/1 頁，共 1 筆",
+        "宋".repeat(24)
+    );
+
+    assert_eq!(strip_ansi_codes(&result_str), right_str);
+    assert!(result_str.contains("\x1b[1m風\x1b[22m\u{2060}吹"));
+    assert_eq!(
+        strip_ansi_codes(&gen_grep_str(
+            &terms,
+            &[],
+            &SearchPage::new(0, 1, 50),
+            false,
+            TERMINAL_LINE_LIMIT_LENGTH
+        )),
+        "風：\n  No matching entries\n  第 1/1 頁，共 0 筆"
+    );
 }
@@ -0,0 +1,334 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    str::FromStr,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Error, Result};
+
+use crate::api::{parse_moedict_entry, strip_link_markup, MoedictDictionary, MoedictRawResult};
+use crate::cache::{grep_index_path, CacheEntry, EntryCache};
+use crate::store::{moedict_store_path, write_sorted_table, SortedTable};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GrepField {
+    Def,
+    Quote,
+    Example,
+}
+
+impl GrepField {
+    pub const ALL: [GrepField; 3] = [GrepField::Def, GrepField::Quote, GrepField::Example];
+
+    /// Definitions say what a word means, so they outrank usages of it
+    fn weight(&self) -> usize {
+        match self {
+            GrepField::Def => 3,
+            GrepField::Example => 2,
+            GrepField::Quote => 1,
+        }
+    }
+}
+
+impl FromStr for GrepField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "def" => Ok(GrepField::Def),
+            "quote" => Ok(GrepField::Quote),
+            "example" => Ok(GrepField::Example),
+            _ => Err(anyhow!("Unsupport grep field: {}", s)),
+        }
+    }
+}
+
+/// Text of a field containing one of the search terms, markup stripped
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrepMatch {
+    pub field: GrepField,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrepHit {
+    pub title: String,
+    pub matches: Vec<GrepMatch>,
+    pub score: usize,
+}
+
+/// Search the definitions, quotes and examples of entries available offline
+/// for entries containing every term. `fields` empty means all of them.
+///
+/// Candidates come from character indexes over the offline store and the
+/// entry cache. The store is only indexed again after an import, while the
+/// much smaller cache index is rebuilt whenever the cache changed.
+pub fn grep_entries(
+    dict: MoedictDictionary,
+    entry_ttl: u64,
+    terms: &[String],
+    fields: &[GrepField],
+) -> Result<Vec<GrepHit>> {
+    let fields = if fields.is_empty() {
+        &GrepField::ALL
+    } else {
+        fields
+    };
+    let cache = EntryCache::new(&format!("moedict_{}", dict.path()), entry_ttl);
+    let store_path = moedict_store_path(dict);
+    let store = match store_path.exists() {
+        true => Some(SortedTable::open(&store_path)?),
+        false => None,
+    };
+    let index_dir = grep_index_path(dict);
+    fs::create_dir_all(&index_dir)?;
+    let mut indexes = Vec::new();
+    if let Some(store) = &store {
+        let index_path = index_dir.join("offline.db");
+        if !is_grep_index_fresh(&index_path, modified(&store_path)) {
+            write_sorted_table(&index_path, build_grep_index(&store.entries()?))?;
+        }
+        indexes.push(SortedTable::open(&index_path)?);
+    }
+    let index_path = index_dir.join("cache.db");
+    if !is_grep_index_fresh(&index_path, cache.modified()) {
+        write_sorted_table(&index_path, build_grep_index(&cached_entries(&cache)))?;
+    }
+    indexes.push(SortedTable::open(&index_path)?);
+    if indexes.iter().all(|x| x.len() == 0) {
+        return Err(anyhow!(
+            "No local entries to search, please import a moedict dump or look up some words first"
+        ));
+    }
+
+    let chars = terms.concat().chars().collect::<HashSet<_>>();
+    if chars.is_empty() {
+        return Err(anyhow!("Please enter words!"));
+    }
+    let mut candidates = HashSet::new();
+    for index in &indexes {
+        candidates.extend(index_candidates(index, &chars)?);
+    }
+    let mut hits = candidates
+        .into_iter()
+        .filter_map(|word| {
+            let entry = local_entry(store.as_ref(), &cache, &word)?;
+            grep_entry(word, &parse_moedict_entry(&entry).ok()?, terms, fields)
+        })
+        .collect::<Vec<_>>();
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(a.title.chars().count().cmp(&b.title.chars().count()))
+            .then(a.title.cmp(&b.title))
+    });
+
+    Ok(hits)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|x| x.modified()).ok()
+}
+
+fn is_grep_index_fresh(index_path: &Path, source_modified: Option<SystemTime>) -> bool {
+    matches!(modified(index_path), Some(x) if Some(x) >= source_modified)
+}
+
+/// Headwords of `index` using every character of `chars`
+fn index_candidates(index: &SortedTable, chars: &HashSet<char>) -> Result<HashSet<String>> {
+    let mut candidates: Option<HashSet<String>> = None;
+    for c in chars {
+        let words = index.get(&c.to_string())?.unwrap_or_default();
+        let words = words.lines().map(|x| x.to_string()).collect::<HashSet<_>>();
+        candidates = Some(match candidates {
+            Some(candidates) => candidates.intersection(&words).cloned().collect(),
+            None => words,
+        });
+    }
+
+    Ok(candidates.unwrap_or_default())
+}
+
+/// Cached entries, leaving out cached radical indexes
+fn cached_entries(cache: &EntryCache) -> Vec<(String, String)> {
+    cache
+        .entries()
+        .into_iter()
+        .filter(|(word, _)| !word.starts_with('@'))
+        .collect()
+}
+
+/// The offline store wins over the entry cache
+fn local_entry(store: Option<&SortedTable>, cache: &EntryCache, word: &str) -> Option<String> {
+    if let Some(Ok(Some(entry))) = store.map(|x| x.get(word)) {
+        return Some(entry);
+    }
+
+    match cache.get(word) {
+        CacheEntry::Fresh(entry) | CacheEntry::Stale(entry) => Some(entry),
+        CacheEntry::Missing => None,
+    }
+}
+
+/// Every character used in the searchable fields, mapped to the headwords
+/// using it one per line
+fn build_grep_index(entries: &[(String, String)]) -> Vec<(String, String)> {
+    let mut postings: HashMap<char, Vec<&str>> = HashMap::new();
+    for (word, entry) in entries {
+        let entry = match parse_moedict_entry(entry) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let chars = field_texts(&entry, &GrepField::ALL)
+            .into_iter()
+            .flat_map(|x| x.text.chars().collect::<Vec<_>>())
+            .filter(|x| !x.is_whitespace())
+            .collect::<HashSet<_>>();
+        for c in chars {
+            postings.entry(c).or_default().push(word);
+        }
+    }
+
+    postings
+        .into_iter()
+        .map(|(c, words)| (c.to_string(), words.join("\n")))
+        .collect()
+}
+
+fn field_texts(entry: &MoedictRawResult, fields: &[GrepField]) -> Vec<GrepMatch> {
+    let mut texts = Vec::new();
+    let definitions = entry
+        .heteronyms
+        .iter()
+        .flatten()
+        .flat_map(|x| x.definitions.iter().flatten());
+    for definition in definitions {
+        for field in fields {
+            let values = match field {
+                GrepField::Def => definition.def.iter().collect::<Vec<_>>(),
+                GrepField::Quote => definition.quote.iter().flatten().collect(),
+                GrepField::Example => definition.example.iter().flatten().collect(),
+            };
+            texts.extend(values.into_iter().map(|x| GrepMatch {
+                field: *field,
+                text: strip_link_markup(x),
+            }));
+        }
+    }
+
+    texts
+}
+
+/// `None` unless every term is found in `fields` of the entry
+fn grep_entry(
+    title: String,
+    entry: &MoedictRawResult,
+    terms: &[String],
+    fields: &[GrepField],
+) -> Option<GrepHit> {
+    let texts = field_texts(entry, fields);
+    if !terms
+        .iter()
+        .all(|term| texts.iter().any(|x| x.text.contains(term.as_str())))
+    {
+        return None;
+    }
+    let matches = texts
+        .into_iter()
+        .filter(|x| terms.iter().any(|term| x.text.contains(term.as_str())))
+        .collect::<Vec<_>>();
+    let score = matches
+        .iter()
+        .map(|x| {
+            let count = terms
+                .iter()
+                .map(|term| x.text.matches(term.as_str()).count())
+                .sum::<usize>();
+            count * x.field.weight()
+        })
+        .sum();
+
+    Some(GrepHit {
+        title,
+        matches,
+        score,
+    })
+}
+
+#[test]
+fn test_grep_entry() {
+    let entries = vec![
+        (
+            "空穴來風".to_string(),
+            r#"{"t":"空穴來風","h":[{"d":[{"f":"有空穴，就有`風~吹來。","q":["宋玉〈風賦〉"]}]}]}"#
+                .to_string(),
+        ),
+        (
+            "論語".to_string(),
+            r#"{"t":"論語","h":[{"d":[{"f":"書名。","e":["如：「熟讀《論語》」"]}]}]}"#.to_string(),
+        ),
+    ];
+    let file = tempfile::NamedTempFile::new().unwrap();
+    write_sorted_table(file.path(), build_grep_index(&entries)).unwrap();
+    let table = SortedTable::open(file.path()).unwrap();
+    let index = build_grep_index(&entries)
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let entry = |index: usize| parse_moedict_entry(&entries[index].1).unwrap();
+    let terms = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(index["風"], "空穴來風");
+    assert_eq!(index["語"], "論語");
+    assert!(!index.contains_key("`"));
+    assert_eq!(
+        index_candidates(&table, &HashSet::from(['風', '吹'])).unwrap(),
+        HashSet::from(["空穴來風".to_string()])
+    );
+    assert!(index_candidates(&table, &HashSet::from(['風', '書']))
+        .unwrap()
+        .is_empty());
+    let hit = grep_entry(
+        "空穴來風".to_string(),
+        &entry(0),
+        &terms(&["風"]),
+        &GrepField::ALL,
+    )
+    .unwrap();
+    assert_eq!(
+        hit.matches,
+        vec![
+            GrepMatch {
+                field: GrepField::Def,
+                text: "有空穴，就有風吹來。".to_string()
+            },
+            GrepMatch {
+                field: GrepField::Quote,
+                text: "宋玉〈風賦〉".to_string()
+            },
+        ]
+    );
+    assert_eq!(hit.score, 4);
+    assert!(grep_entry(
+        "空穴來風".to_string(),
+        &entry(0),
+        &terms(&["風"]),
+        &[GrepField::Example]
+    )
+    .is_none());
+    assert!(grep_entry(
+        "論語".to_string(),
+        &entry(1),
+        &terms(&["論語", "書"]),
+        &GrepField::ALL
+    )
+    .is_some());
+    assert!(grep_entry(
+        "論語".to_string(),
+        &entry(1),
+        &terms(&["論語", "風"]),
+        &GrepField::ALL
+    )
+    .is_none());
+}
@@ -1,17 +1,31 @@
+pub mod annotate;
 pub mod api;
+mod cache;
+pub mod cantonese;
 mod cli;
+mod client;
 pub mod console;
 mod feat;
 pub mod formatter;
+pub mod grep;
+pub mod mandarin;
+pub mod pattern;
+mod store;
 
 use std::{
     fs::create_dir_all,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use crate::console::MeowdictConsole;
+use crate::annotate::{annotate_text, gen_annotation_str};
+use crate::api::MoedictDictionary;
+use crate::cache::MeowdictCacheConfig;
+use crate::cantonese::CantoneseRomanization;
+use crate::client::{MeowdictBackends, MeowdictClient};
+use crate::console::{MeowdictConsole, NavigationHistory};
 use crate::feat::*;
+use crate::mandarin::{romanize, PinyinStyle};
 use anyhow::Result;
 use clap::ArgMatches;
 use lazy_static::lazy_static;
@@ -24,11 +38,17 @@ lazy_static! {
     static ref CONFIG_PATH: PathBuf = CONFTG_PATH_DIRECTORY.join("meowdict.toml");
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
 pub struct MeowdictConfig {
     input_s2t: bool,
     result_t2s: bool,
     no_color: bool,
+    dict: MoedictDictionary,
+    romanization: CantoneseRomanization,
+    hyperlinks: bool,
+    backends: MeowdictBackends,
+    cache: MeowdictCacheConfig,
 }
 
 pub struct MeowdictRunStatus {
@@ -39,26 +59,81 @@ pub struct MeowdictRunStatus {
     pub words: Option<Vec<String>>,
 }
 
-impl Default for MeowdictConfig {
-    fn default() -> Self {
-        MeowdictConfig {
-            input_s2t: false,
-            result_t2s: false,
-            no_color: false,
-        }
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = read_config()?;
     let app = cli::build_cli().get_matches();
-    let client = reqwest::Client::new();
-    let mut input_s2t = config.input_s2t || app.contains_id("inputs2t");
-    let mut result_t2s = config.result_t2s || app.contains_id("resultt2s");
-    let mut no_color = config.no_color || app.contains_id("no-color-output");
+    let client = MeowdictClient::new(
+        config.backends.with_env(),
+        config.cache,
+        get_flag(&app, "offline"),
+    );
+    let mut input_s2t = config.input_s2t || get_flag(&app, "inputs2t");
+    let mut result_t2s = config.result_t2s || get_flag(&app, "resultt2s");
+    let mut no_color = config.no_color || get_flag(&app, "no-color-output");
+    let hyperlinks = config.hyperlinks || get_flag(&app, "hyperlinks");
+    let mut dict = get_dict(&app)?.unwrap_or(config.dict);
+    let mut romanization = get_romanization(&app)?.unwrap_or(config.romanization);
+    let mut pinyin_style = get_pinyin_style(&app, "pinyin-style")?;
     let subcmd = app.subcommand();
+    if let Some((_, args)) = subcmd {
+        dict = get_dict(args)?.unwrap_or(dict);
+        romanization = get_romanization(args)?.unwrap_or(romanization);
+        pinyin_style = get_pinyin_style(args, "pinyin-style")?.or(pinyin_style);
+    }
+    if let Some(("romanize", args)) = subcmd {
+        let input = args
+            .get_many::<String>("INPUT")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let from = get_pinyin_style(args, "from")?;
+        let to = get_pinyin_style(args, "to")?.unwrap_or(PinyinStyle::Numbered);
+        println!("{}", romanize(&input, from, to));
+
+        return Ok(());
+    }
+    if let Some(("annotate", args)) = subcmd {
+        let text = match args.get_one::<String>("FILE").map(|x| x.as_str()) {
+            Some("-") | None => {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            }
+            Some(path) => std::fs::read_to_string(path)?,
+        };
+        let reading = match args.get_one::<String>("reading") {
+            Some(reading) => reading.parse()?,
+            None => Default::default(),
+        };
+        let format = match args.get_one::<String>("format") {
+            Some(format) => format.parse()?,
+            None => Default::default(),
+        };
+        let annotations = annotate_text(&client, &text, dict, reading, romanization).await?;
+        println!("{}", gen_annotation_str(&annotations, format));
+
+        return Ok(());
+    }
+    if let Some(("import", args)) = subcmd {
+        let dump_path = args.get_one::<String>("FILE").unwrap();
+        let store_path = store::moedict_store_path(dict);
+        let count = store::import_moedict_dump(Path::new(dump_path), &store_path)?;
+        println!("Imported {} entries to {}", count, store_path.display());
+
+        return Ok(());
+    }
+    if let Some(("cache", args)) = subcmd {
+        let cache_subcmd = args.subcommand();
+        if let Some((_, args)) = cache_subcmd {
+            dict = get_dict(args)?.unwrap_or(dict);
+        }
+
+        return run_cache_command(&client, cache_subcmd, dict, no_color).await;
+    }
     let mut is_all = false;
+    let mut json = false;
     if !is_meowdict_terminal(&app) {
         if let Some(words) = app.get_many::<String>("INPUT") {
             let words = words.into_iter().map(|x| x.into()).collect::<Vec<String>>();
@@ -70,6 +145,14 @@ async fn main() -> Result<()> {
                 no_color,
                 words: Some(words),
                 is_all,
+                json,
+                dict,
+                romanization,
+                pinyin_style,
+                hyperlinks,
+                numbered: false,
+                references: Vec::new(),
+                search: SearchOptions::default(),
             }
             .match_command_to_run()
             .await
@@ -79,20 +162,33 @@ async fn main() -> Result<()> {
                 "translate" => MeowdictRunCommand::Translate,
                 "jyutping" => MeowdictRunCommand::JyutPing,
                 "random" => MeowdictRunCommand::Random,
+                "json" => MeowdictRunCommand::Json,
+                "reverse" => MeowdictRunCommand::Reverse,
+                "radical" => MeowdictRunCommand::Radical,
+                "segment" => MeowdictRunCommand::Segment,
+                "search" => MeowdictRunCommand::Search,
+                "grep" => MeowdictRunCommand::Grep,
                 _ => unreachable!(),
             };
             let mut words = None;
+            let mut open = None;
+            let mut search = SearchOptions::default();
             if let Some((_, args)) = subcmd {
+                open = get_usize(args, "open");
+                search = get_search_options(args)?;
                 words = args
                     .get_many::<String>("INPUT")
                     .map(|x| x.cloned().collect());
-                input_s2t = input_s2t || args.contains_id("inputs2t");
-                result_t2s = result_t2s || args.contains_id("resultt2s");
-                no_color = no_color || args.contains_id("no-color-output");
-                is_all = args.contains_id("all");
+                input_s2t = input_s2t || get_flag(args, "inputs2t");
+                result_t2s = result_t2s || get_flag(args, "resultt2s");
+                no_color = no_color || get_flag(args, "no-color-output");
+                is_all = get_flag(args, "all");
+                json = get_flag(args, "json");
             }
 
-            MeowdictResponse {
+            // Numbers of the character list are what `--open` takes
+            let numbered = matches!(command, MeowdictRunCommand::Radical);
+            let mut response = MeowdictResponse {
                 command,
                 client: &client,
                 input_s2t,
@@ -100,19 +196,30 @@ async fn main() -> Result<()> {
                 no_color,
                 words,
                 is_all,
+                json,
+                dict,
+                romanization,
+                pinyin_style,
+                hyperlinks,
+                numbered,
+                references: Vec::new(),
+                search,
+            };
+
+            match open {
+                Some(number) => response.open_radical_character(number).await,
+                None => response.match_command_to_run().await,
             }
-            .match_command_to_run()
-            .await
         }
     } else {
-        let mut input_s2t_mode = config.input_s2t || app.contains_id("inputs2tmode");
-        let mut result_t2s_mode = config.result_t2s || app.contains_id("resultt2smode");
-        let mut no_color = config.no_color || app.contains_id("no-color-output");
+        let mut input_s2t_mode = config.input_s2t || get_flag(&app, "inputs2tmode");
+        let mut result_t2s_mode = config.result_t2s || get_flag(&app, "resultt2smode");
+        let mut no_color = config.no_color || get_flag(&app, "no-color-output");
         if let Some((cmd, args)) = subcmd {
             if cmd == "terminal" {
-                input_s2t_mode = input_s2t_mode || args.contains_id("inputs2tmode");
-                result_t2s_mode = result_t2s_mode || args.contains_id("resultt2smode");
-                no_color = no_color || args.contains_id("no-color-output");
+                input_s2t_mode = input_s2t_mode || get_flag(args, "inputs2tmode");
+                result_t2s_mode = result_t2s_mode || get_flag(args, "resultt2smode");
+                no_color = no_color || get_flag(args, "no-color-output");
             }
         }
         let mut console = MeowdictConsole {
@@ -120,6 +227,12 @@ async fn main() -> Result<()> {
             input_s2t: input_s2t_mode,
             result_t2s: result_t2s_mode,
             no_color,
+            dict,
+            romanization,
+            pinyin_style,
+            hyperlinks,
+            links: Vec::new(),
+            history: NavigationHistory::default(),
         };
 
         console.create_console().await
@@ -131,12 +244,60 @@ fn is_meowdict_terminal(app: &ArgMatches) -> bool {
         || app.subcommand_name() == Some("terminal")
 }
 
+/// Not every subcommand defines every flag, so treat unknown ids as unset
+fn get_flag(args: &ArgMatches, id: &str) -> bool {
+    matches!(args.try_get_one::<bool>(id), Ok(Some(true)))
+}
+
+fn get_usize(args: &ArgMatches, id: &str) -> Option<usize> {
+    args.try_get_one::<usize>(id).ok().flatten().copied()
+}
+
+fn get_search_options(args: &ArgMatches) -> Result<SearchOptions> {
+    let default = SearchOptions::default();
+    let fields = match args.try_get_many::<String>("field") {
+        Ok(Some(fields)) => fields.map(|x| x.parse()).collect::<Result<_>>()?,
+        _ => Vec::new(),
+    };
+
+    Ok(SearchOptions {
+        min_length: get_usize(args, "min-length"),
+        max_length: get_usize(args, "max-length"),
+        page: get_usize(args, "page").unwrap_or(default.page),
+        per_page: get_usize(args, "per-page").unwrap_or(default.per_page),
+        full: get_flag(args, "full"),
+        fields,
+    })
+}
+
+fn get_dict(args: &ArgMatches) -> Result<Option<MoedictDictionary>> {
+    match args.try_get_one::<String>("dict") {
+        Ok(Some(dict)) => Ok(Some(dict.parse()?)),
+        _ => Ok(None),
+    }
+}
+
+fn get_romanization(args: &ArgMatches) -> Result<Option<CantoneseRomanization>> {
+    match args.try_get_one::<String>("romanization") {
+        Ok(Some(romanization)) => Ok(Some(romanization.parse()?)),
+        _ => Ok(None),
+    }
+}
+
+fn get_pinyin_style(args: &ArgMatches, id: &str) -> Result<Option<PinyinStyle>> {
+    match args.try_get_one::<String>(id) {
+        Ok(Some(style)) => Ok(Some(style.parse()?)),
+        _ => Ok(None),
+    }
+}
+
 fn read_config() -> Result<MeowdictConfig> {
     create_dir_all(&*CONFTG_PATH_DIRECTORY)?;
     let mut file = OpenOptions::new()
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(&*CONFIG_PATH)?;
     let mut buffer = String::new();
     file.read_to_string(&mut buffer)?;
@@ -145,7 +306,7 @@ fn read_config() -> Result<MeowdictConfig> {
         Ok(config) => config,
         Err(_) => {
             let default = MeowdictConfig::default();
-            file.write_all(&toml::to_string(&default)?.as_bytes())?;
+            file.write_all(toml::to_string(&default)?.as_bytes())?;
 
             default
         }
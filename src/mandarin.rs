@@ -0,0 +1,729 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+/// Pinyin initials with their zhuyin, longest first so `zh` wins over `z`
+const INITIALS: [(&str, &str); 21] = [
+    ("zh", "ㄓ"),
+    ("ch", "ㄔ"),
+    ("sh", "ㄕ"),
+    ("b", "ㄅ"),
+    ("p", "ㄆ"),
+    ("m", "ㄇ"),
+    ("f", "ㄈ"),
+    ("d", "ㄉ"),
+    ("t", "ㄊ"),
+    ("n", "ㄋ"),
+    ("l", "ㄌ"),
+    ("g", "ㄍ"),
+    ("k", "ㄎ"),
+    ("h", "ㄏ"),
+    ("j", "ㄐ"),
+    ("q", "ㄑ"),
+    ("x", "ㄒ"),
+    ("r", "ㄖ"),
+    ("z", "ㄗ"),
+    ("c", "ㄘ"),
+    ("s", "ㄙ"),
+];
+
+/// Finals in their full pinyin form (`iou`, `uei`, `uen`, `ü`) with zhuyin.
+/// `-i` is the empty rime of `zhi`, `ci` and friends.
+const FINALS: [(&str, &str); 39] = [
+    ("a", "ㄚ"),
+    ("o", "ㄛ"),
+    ("e", "ㄜ"),
+    ("ê", "ㄝ"),
+    ("ai", "ㄞ"),
+    ("ei", "ㄟ"),
+    ("ao", "ㄠ"),
+    ("ou", "ㄡ"),
+    ("an", "ㄢ"),
+    ("en", "ㄣ"),
+    ("ang", "ㄤ"),
+    ("eng", "ㄥ"),
+    ("ong", "ㄨㄥ"),
+    ("er", "ㄦ"),
+    ("-i", ""),
+    ("i", "ㄧ"),
+    ("ia", "ㄧㄚ"),
+    ("io", "ㄧㄛ"),
+    ("ie", "ㄧㄝ"),
+    ("iai", "ㄧㄞ"),
+    ("iao", "ㄧㄠ"),
+    ("iou", "ㄧㄡ"),
+    ("ian", "ㄧㄢ"),
+    ("in", "ㄧㄣ"),
+    ("iang", "ㄧㄤ"),
+    ("ing", "ㄧㄥ"),
+    ("iong", "ㄩㄥ"),
+    ("u", "ㄨ"),
+    ("ua", "ㄨㄚ"),
+    ("uo", "ㄨㄛ"),
+    ("uai", "ㄨㄞ"),
+    ("uei", "ㄨㄟ"),
+    ("uan", "ㄨㄢ"),
+    ("uen", "ㄨㄣ"),
+    ("uang", "ㄨㄤ"),
+    ("ueng", "ㄨㄥ"),
+    ("ü", "ㄩ"),
+    ("üe", "ㄩㄝ"),
+    ("üan", "ㄩㄢ"),
+];
+
+/// `ün` does not fit in `FINALS` ordering above, kept apart for readability
+const FINAL_UN: (&str, &str) = ("ün", "ㄩㄣ");
+
+const TONE_MARKS: [(char, [char; 4]); 6] = [
+    ('a', ['ā', 'á', 'ǎ', 'à']),
+    ('e', ['ē', 'é', 'ě', 'è']),
+    ('i', ['ī', 'í', 'ǐ', 'ì']),
+    ('o', ['ō', 'ó', 'ǒ', 'ò']),
+    ('u', ['ū', 'ú', 'ǔ', 'ù']),
+    ('ü', ['ǖ', 'ǘ', 'ǚ', 'ǜ']),
+];
+
+const ZHUYIN_TONES: [char; 4] = ['ˉ', 'ˊ', 'ˇ', 'ˋ'];
+const ZHUYIN_NEUTRAL_TONE: char = '˙';
+
+lazy_static! {
+    static ref WADE_GILES_SYLLABLES: HashMap<String, MandarinSyllable> = {
+        let mut syllables = HashMap::new();
+        for syllable in all_syllables(1..=1) {
+            let wade_giles = syllable.to_wade_giles_base();
+            syllables
+                .entry(wade_giles.replace('ê', "e"))
+                .or_insert_with(|| syllable.clone());
+            syllables.entry(wade_giles).or_insert(syllable);
+        }
+
+        syllables
+    };
+    static ref GWOYEU_ROMATZYH_SYLLABLES: HashMap<String, MandarinSyllable> = {
+        let mut syllables = HashMap::new();
+        for syllable in all_syllables(1..=5) {
+            syllables
+                .entry(syllable.to_gwoyeu_romatzyh())
+                .or_insert(syllable);
+        }
+
+        syllables
+    };
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PinyinStyle {
+    #[default]
+    Pinyin,
+    Numbered,
+    Zhuyin,
+    WadeGiles,
+    GwoyeuRomatzyh,
+}
+
+impl fmt::Display for PinyinStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PinyinStyle::Pinyin => "pinyin",
+            PinyinStyle::Numbered => "numbered",
+            PinyinStyle::Zhuyin => "zhuyin",
+            PinyinStyle::WadeGiles => "wade-giles",
+            PinyinStyle::GwoyeuRomatzyh => "gwoyeu-romatzyh",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for PinyinStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pinyin" => Ok(PinyinStyle::Pinyin),
+            "numbered" => Ok(PinyinStyle::Numbered),
+            "zhuyin" | "bopomofo" => Ok(PinyinStyle::Zhuyin),
+            "wade-giles" => Ok(PinyinStyle::WadeGiles),
+            "gwoyeu-romatzyh" => Ok(PinyinStyle::GwoyeuRomatzyh),
+            _ => Err(anyhow!("Unsupport pinyin style: {}", s)),
+        }
+    }
+}
+
+/// One Mandarin syllable. `tone` is 1 to 4, or 5 for the neutral tone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MandarinSyllable {
+    initial: String,
+    final_: String,
+    tone: u8,
+}
+
+impl MandarinSyllable {
+    fn new(initial: &str, final_: &str, tone: u8) -> Option<Self> {
+        if !is_valid_syllable(initial, final_) {
+            return None;
+        }
+
+        Some(MandarinSyllable {
+            initial: initial.to_string(),
+            final_: final_.to_string(),
+            tone,
+        })
+    }
+
+    /// Parse a syllable written in any style, trying pinyin before the other
+    /// romanizations where a spelling is valid in more than one
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            PinyinStyle::Zhuyin,
+            PinyinStyle::Pinyin,
+            PinyinStyle::Numbered,
+            PinyinStyle::WadeGiles,
+            PinyinStyle::GwoyeuRomatzyh,
+        ]
+        .into_iter()
+        .find_map(|style| MandarinSyllable::parse_as(s, style))
+    }
+
+    pub fn parse_as(s: &str, style: PinyinStyle) -> Option<Self> {
+        let s = s.to_lowercase();
+        match style {
+            PinyinStyle::Pinyin => parse_marked_pinyin(&s),
+            PinyinStyle::Numbered => {
+                let (base, tone) = split_tone_number(&s)?;
+                parse_pinyin_base(&base.replace("u:", "ü").replace('v', "ü"), tone)
+            }
+            PinyinStyle::Zhuyin => parse_zhuyin(&s),
+            PinyinStyle::WadeGiles => {
+                let (base, tone) = split_tone_number(&s).unwrap_or((&s, 5));
+                let base = base.replace(['’', '‘', 'ʻ', '`'], "'");
+                let syllable = WADE_GILES_SYLLABLES.get(&base)?;
+                Some(MandarinSyllable {
+                    tone,
+                    ..syllable.clone()
+                })
+            }
+            PinyinStyle::GwoyeuRomatzyh => GWOYEU_ROMATZYH_SYLLABLES.get(&s).cloned(),
+        }
+    }
+
+    pub fn to_style(&self, style: PinyinStyle) -> String {
+        match style {
+            PinyinStyle::Pinyin => add_pinyin_tone_mark(&self.to_pinyin_base(), self.tone),
+            PinyinStyle::Numbered => format!("{}{}", self.to_pinyin_base(), self.tone),
+            PinyinStyle::Zhuyin => self.to_zhuyin(),
+            PinyinStyle::WadeGiles => match self.tone {
+                5 => self.to_wade_giles_base(),
+                tone => format!("{}{}", self.to_wade_giles_base(), tone),
+            },
+            PinyinStyle::GwoyeuRomatzyh => self.to_gwoyeu_romatzyh(),
+        }
+    }
+
+    fn to_pinyin_base(&self) -> String {
+        let initial = self.initial.as_str();
+        if initial.is_empty() {
+            return match self.final_.as_str() {
+                "i" | "in" | "ing" => format!("y{}", self.final_),
+                "u" => "wu".to_string(),
+                "ü" | "üe" | "üan" | "ün" => self.final_.replacen('ü', "yu", 1),
+                x if x.starts_with('i') => x.replacen('i', "y", 1),
+                x if x.starts_with('u') => x.replacen('u', "w", 1),
+                x => x.to_string(),
+            };
+        }
+        let final_ = match self.final_.as_str() {
+            "-i" => "i".to_string(),
+            "iou" => "iu".to_string(),
+            "uei" => "ui".to_string(),
+            "uen" => "un".to_string(),
+            x if matches!(initial, "j" | "q" | "x") => x.replace('ü', "u"),
+            x => x.to_string(),
+        };
+
+        format!("{}{}", initial, final_)
+    }
+
+    fn to_zhuyin(&self) -> String {
+        let initial = INITIALS
+            .iter()
+            .find(|x| x.0 == self.initial)
+            .map(|x| x.1)
+            .unwrap_or_default();
+        let final_ = match self.final_.as_str() {
+            // `ㄨㄥ` is `ong` after an initial and `weng` on its own
+            "ong" => "ㄨㄥ",
+            x => all_finals()
+                .find(|y| y.0 == x)
+                .map(|y| y.1)
+                .unwrap_or_default(),
+        };
+        match self.tone {
+            1 => format!("{}{}", initial, final_),
+            5 => format!("{}{}{}", ZHUYIN_NEUTRAL_TONE, initial, final_),
+            tone => format!("{}{}{}", initial, final_, ZHUYIN_TONES[tone as usize - 1]),
+        }
+    }
+
+    fn to_wade_giles_base(&self) -> String {
+        let initial = self.initial.as_str();
+        let final_ = self.final_.as_str();
+        if initial.is_empty() {
+            let syllable = match final_ {
+                "e" => "o",
+                "en" => "ên",
+                "eng" => "êng",
+                "er" => "êrh",
+                "i" => "i",
+                "ie" => "yeh",
+                "iou" => "yu",
+                "ian" => "yen",
+                "iong" => "yung",
+                "in" | "ing" => return format!("y{}", final_),
+                "u" => "wu",
+                "uen" => "wên",
+                "ueng" => "wêng",
+                "ü" => "yü",
+                "üe" => "yüeh",
+                x if x.starts_with('ü') => return x.replacen('ü', "yü", 1),
+                x if x.starts_with('i') => return x.replacen('i', "y", 1),
+                x if x.starts_with('u') => return x.replacen('u', "w", 1),
+                x => x,
+            };
+            return syllable.to_string();
+        }
+        let wade_giles_initial = match initial {
+            "b" => "p",
+            "p" => "p'",
+            "d" => "t",
+            "t" => "t'",
+            "g" => "k",
+            "k" => "k'",
+            "j" | "zh" => "ch",
+            "q" | "ch" => "ch'",
+            "x" => "hs",
+            "r" => "j",
+            "z" => "ts",
+            "c" => "ts'",
+            x => x,
+        };
+        let wade_giles_final = match final_ {
+            "-i" => {
+                return match initial {
+                    "z" => "tzu".to_string(),
+                    "c" => "tz'u".to_string(),
+                    "s" => "ssu".to_string(),
+                    _ => format!("{}ih", wade_giles_initial),
+                }
+            }
+            "e" if matches!(initial, "g" | "k" | "h") => "o",
+            "e" => "ê",
+            "en" => "ên",
+            "eng" => "êng",
+            "ong" => "ung",
+            "ie" => "ieh",
+            "iou" => "iu",
+            "ian" => "ien",
+            "iong" => "iung",
+            "uo" if matches!(
+                initial,
+                "d" | "t" | "n" | "l" | "z" | "c" | "s" | "zh" | "ch" | "r"
+            ) =>
+            {
+                "o"
+            }
+            "uei" if !matches!(initial, "g" | "k") => "ui",
+            "uen" => "un",
+            "üe" => "üeh",
+            x => x,
+        };
+
+        format!("{}{}", wade_giles_initial, wade_giles_final)
+    }
+
+    fn to_gwoyeu_romatzyh(&self) -> String {
+        let initial = match self.initial.as_str() {
+            "q" => "ch",
+            "x" => "sh",
+            "zh" => "j",
+            "z" => "tz",
+            "c" => "ts",
+            x => x,
+        };
+        let basic = match self.final_.as_str() {
+            "ao" => "au".to_string(),
+            "er" => "el".to_string(),
+            "-i" => "y".to_string(),
+            "iao" => "iau".to_string(),
+            x => x.replace('ü', "iu"),
+        };
+        let is_sonorant = matches!(initial, "m" | "n" | "l" | "r");
+        let final_ = match self.tone {
+            1 if is_sonorant => return format!("{}h{}", initial, basic),
+            2 if is_sonorant => basic,
+            2 => gwoyeu_romatzyh_second_tone(&basic),
+            3 => {
+                let third = gwoyeu_romatzyh_third_tone(&basic);
+                if !initial.is_empty() {
+                    return format!("{}{}", initial, third);
+                }
+                // `ie` and `uo` keep the medial as `y` / `w`, others prefix it
+                let second = basic.chars().nth(1);
+                return match basic.chars().next() {
+                    Some(x @ ('i' | 'u'))
+                        if third.starts_with(x) && matches!(second, Some('e' | 'o')) =>
+                    {
+                        third.replacen(x, if x == 'i' { "y" } else { "w" }, 1)
+                    }
+                    Some('i') => format!("y{}", third),
+                    Some('u') => format!("w{}", third),
+                    _ => third,
+                };
+            }
+            4 => gwoyeu_romatzyh_fourth_tone(&basic),
+            _ => basic,
+        };
+        // First and neutral tones keep the bare final, as in `i` and `uen`
+        let syllable = if initial.is_empty() && self.tone == 4 {
+            gwoyeu_romatzyh_null_initial(&final_)
+        } else {
+            format!("{}{}", initial, final_)
+        };
+
+        match self.tone {
+            5 => format!(".{}", syllable),
+            _ => syllable,
+        }
+    }
+}
+
+fn all_finals() -> impl Iterator<Item = &'static (&'static str, &'static str)> {
+    FINALS.iter().chain([&FINAL_UN])
+}
+
+fn all_syllables(tones: std::ops::RangeInclusive<u8>) -> Vec<MandarinSyllable> {
+    let mut syllables = Vec::new();
+    for initial in [""].into_iter().chain(INITIALS.iter().map(|x| x.0)) {
+        for (final_, _) in all_finals() {
+            for tone in tones.clone() {
+                syllables.extend(MandarinSyllable::new(initial, final_, tone));
+            }
+        }
+    }
+
+    syllables
+}
+
+/// Rough phonotactics, enough to keep romanizations that reuse letters (such
+/// as Wade-Giles `ch` for both `zh` and `j`) unambiguous
+fn is_valid_syllable(initial: &str, final_: &str) -> bool {
+    if !all_finals().any(|x| x.0 == final_) {
+        return false;
+    }
+    let medial = final_.chars().next();
+    match (initial, final_) {
+        ("", "-i" | "ong") => false,
+        ("", _) => true,
+        (_, "ê" | "er" | "ueng" | "io" | "iai") => false,
+        ("z" | "c" | "s" | "zh" | "ch" | "sh" | "r", "-i") => true,
+        (_, "-i") => false,
+        ("b" | "p" | "m" | "f", "o") => true,
+        (_, "o") => false,
+        ("b" | "p" | "m" | "f", "ong") => false,
+        ("j" | "q" | "x", _) => matches!(medial, Some('i' | 'ü')),
+        ("b" | "p" | "m" | "f", _) if medial == Some('u') => final_ == "u",
+        ("f", _) if medial == Some('i') => false,
+        ("n" | "l", _) if medial == Some('ü') => true,
+        (_, _) if medial == Some('ü') => false,
+        ("b" | "p" | "m" | "d" | "t" | "n" | "l", _) => true,
+        (_, _) => medial != Some('i'),
+    }
+}
+
+fn split_tone_number(s: &str) -> Option<(&str, u8)> {
+    let tone = s
+        .chars()
+        .last()?
+        .to_digit(10)
+        .filter(|x| (1..=5).contains(x))? as u8;
+
+    Some((&s[..s.len() - 1], tone))
+}
+
+fn parse_marked_pinyin(s: &str) -> Option<MandarinSyllable> {
+    let mut tone = 5;
+    let base = s
+        .chars()
+        .map(|c| {
+            for (vowel, marked) in TONE_MARKS {
+                if let Some(index) = marked.iter().position(|x| *x == c) {
+                    tone = index as u8 + 1;
+                    return vowel;
+                }
+            }
+            c
+        })
+        .collect::<String>();
+
+    parse_pinyin_base(&base, tone)
+}
+
+fn parse_pinyin_base(base: &str, tone: u8) -> Option<MandarinSyllable> {
+    if base.is_empty()
+        || !base
+            .chars()
+            .all(|x| x.is_ascii_lowercase() || x == 'ü' || x == 'ê')
+    {
+        return None;
+    }
+    if let Some(rest) = base.strip_prefix('y') {
+        let final_ = match rest {
+            "i" | "in" | "ing" => rest.to_string(),
+            "u" | "ue" | "uan" | "un" => rest.replacen('u', "ü", 1),
+            "ou" => "iou".to_string(),
+            x => format!("i{}", x),
+        };
+        return MandarinSyllable::new("", &final_, tone);
+    }
+    if let Some(rest) = base.strip_prefix('w') {
+        let final_ = match rest {
+            "u" => "u".to_string(),
+            x => format!("u{}", x),
+        };
+        return MandarinSyllable::new("", &final_, tone);
+    }
+    let initial = INITIALS
+        .iter()
+        .map(|x| x.0)
+        .find(|x| base.starts_with(x) && base.len() > x.len())
+        .unwrap_or("");
+    let rest = &base[initial.len()..];
+    let final_ = match rest {
+        "i" if matches!(initial, "z" | "c" | "s" | "zh" | "ch" | "sh" | "r") => "-i".to_string(),
+        x if matches!(initial, "j" | "q" | "x") && x.starts_with('u') => x.replacen('u', "ü", 1),
+        "iu" => "iou".to_string(),
+        "ui" => "uei".to_string(),
+        "un" => "uen".to_string(),
+        x => x.to_string(),
+    };
+
+    MandarinSyllable::new(initial, &final_, tone)
+}
+
+fn parse_zhuyin(s: &str) -> Option<MandarinSyllable> {
+    let mut tone = 1;
+    let mut body = String::new();
+    for c in s.chars() {
+        if c == ZHUYIN_NEUTRAL_TONE {
+            tone = 5;
+        } else if let Some(index) = ZHUYIN_TONES.iter().position(|x| *x == c) {
+            tone = index as u8 + 1;
+        } else {
+            body.push(c);
+        }
+    }
+    if body.is_empty() || !body.chars().all(|x| ('ㄅ'..='ㄩ').contains(&x)) {
+        return None;
+    }
+    let (initial, rest) = match INITIALS.iter().find(|x| body.starts_with(x.1)) {
+        Some((initial, zhuyin)) => (*initial, &body[zhuyin.len()..]),
+        None => ("", body.as_str()),
+    };
+    let final_ = match rest {
+        "ㄨㄥ" if initial.is_empty() => "ueng",
+        "ㄨㄥ" => "ong",
+        x => all_finals().find(|y| y.1 == x)?.0,
+    };
+
+    MandarinSyllable::new(initial, final_, tone)
+}
+
+/// Put the tone mark on `a` or `e`, on the `o` of `ou`, or else on the last vowel
+fn add_pinyin_tone_mark(base: &str, tone: u8) -> String {
+    if tone == 5 {
+        return base.to_string();
+    }
+    let position = base
+        .find('a')
+        .or_else(|| base.find('e'))
+        .or_else(|| base.find("ou"))
+        .or_else(|| base.rfind(['i', 'o', 'u', 'ü']));
+    let position = match position {
+        Some(position) => position,
+        None => return base.to_string(),
+    };
+
+    base.char_indices()
+        .map(|(index, c)| match TONE_MARKS.iter().find(|x| x.0 == c) {
+            Some((_, marked)) if index == position => marked[tone as usize - 1],
+            _ => c,
+        })
+        .collect()
+}
+
+/// Second tone: medials become `y` / `w`, other finals take an `r`
+fn gwoyeu_romatzyh_second_tone(basic: &str) -> String {
+    match basic {
+        "i" => "yi".to_string(),
+        "u" => "wu".to_string(),
+        x if x.starts_with('i') => x.replacen('i', "y", 1),
+        x if x.starts_with('u') => x.replacen('u', "w", 1),
+        x if x.ends_with(['i', 'u']) || !x.ends_with(['n', 'g', 'l']) => format!("{}r", x),
+        x => {
+            let coda = if x.ends_with("ng") { 2 } else { 1 };
+            format!("{}r{}", &x[..x.len() - coda], &x[x.len() - coda..])
+        }
+    }
+}
+
+/// Third tone: single vowels double, medials `i` / `u` become `e` / `o`
+fn gwoyeu_romatzyh_third_tone(basic: &str) -> String {
+    let third = match basic {
+        "a" => "aa",
+        "o" => "oo",
+        "e" => "ee",
+        "i" => "ii",
+        "u" => "uu",
+        "y" => "yy",
+        "el" => "eel",
+        "iu" => "eu",
+        "ai" => "ae",
+        "au" => "ao",
+        "ei" => "eei",
+        "ou" => "oou",
+        "an" => "aan",
+        "en" => "een",
+        "ang" => "aang",
+        "eng" => "eeng",
+        "ong" => "oong",
+        "ie" => "iee",
+        "in" => "iin",
+        "ing" => "iing",
+        "uo" => "uoo",
+        x if x.starts_with("iu") => return x.replacen("iu", "eu", 1),
+        x if x.starts_with('i') => return x.replacen('i', "e", 1),
+        x if x.starts_with('u') => return x.replacen('u', "o", 1),
+        x => x,
+    };
+
+    third.to_string()
+}
+
+/// Fourth tone: final `i` / `u` become `y` / `w`, `n` / `ng` / `l` double up
+/// as `nn` / `nq` / `ll`, anything else takes an `h`
+fn gwoyeu_romatzyh_fourth_tone(basic: &str) -> String {
+    match basic {
+        "i" | "u" | "iu" | "y" => format!("{}h", basic),
+        x if x.ends_with("ng") => format!("{}q", &x[..x.len() - 1]),
+        x if x.ends_with('n') => format!("{}n", x),
+        x if x.ends_with('l') => format!("{}l", x),
+        x if x.ends_with('i') => format!("{}y", &x[..x.len() - 1]),
+        x if x.ends_with('u') => format!("{}w", &x[..x.len() - 1]),
+        x => format!("{}h", x),
+    }
+}
+
+fn gwoyeu_romatzyh_null_initial(final_: &str) -> String {
+    let second = final_.chars().nth(1);
+    match final_.chars().next() {
+        Some('i') if matches!(second, Some('a' | 'e' | 'o' | 'u')) => final_.replacen('i', "y", 1),
+        Some('i') => format!("y{}", final_),
+        Some('u') if matches!(second, Some('a' | 'e' | 'o')) => final_.replacen('u', "w", 1),
+        Some('u') => format!("w{}", final_),
+        _ => final_.to_string(),
+    }
+}
+
+/// Convert every syllable of `s` to `to`, reading it as `from` or guessing the
+/// style of each syllable. Anything that is not a syllable is kept as is.
+pub fn romanize(s: &str, from: Option<PinyinStyle>, to: PinyinStyle) -> String {
+    s.split_whitespace()
+        .map(|token| {
+            // Leave labels such as `（語音）` in front of the reading alone
+            let (label, token) = match token.rfind('）') {
+                Some(index) => token.split_at(index + '）'.len_utf8()),
+                None => ("", token),
+            };
+            let syllable = match from {
+                Some(from) => MandarinSyllable::parse_as(token, from),
+                None => MandarinSyllable::parse(token),
+            };
+            match syllable {
+                Some(syllable) => format!("{}{}", label, syllable.to_style(to)),
+                None => format!("{}{}", label, token),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn test_romanize() {
+    let convert = |s, to| romanize(s, None, to);
+
+    assert_eq!(
+        convert("kōng xué lái fēng", PinyinStyle::Numbered),
+        "kong1 xue2 lai2 feng1"
+    );
+    assert_eq!(
+        convert("kong1 xue2 lai2 feng1", PinyinStyle::Pinyin),
+        "kōng xué lái fēng"
+    );
+    assert_eq!(
+        convert("ㄎㄨㄥ　ㄒㄩㄝˊ　ㄌㄞˊ　ㄈㄥ", PinyinStyle::Numbered),
+        "kong1 xue2 lai2 feng1"
+    );
+    assert_eq!(
+        convert("lǜ yǒu zhī de", PinyinStyle::Zhuyin),
+        "ㄌㄩˋ ㄧㄡˇ ㄓ ˙ㄉㄜ"
+    );
+    assert_eq!(
+        convert("wēng guǐ qióng ér", PinyinStyle::Zhuyin),
+        "ㄨㄥ ㄍㄨㄟˇ ㄑㄩㄥˊ ㄦˊ"
+    );
+    assert_eq!(
+        convert("kōng xué zhī sì guó rén", PinyinStyle::WadeGiles),
+        "k'ung1 hsüeh2 chih1 ssu4 kuo2 jên2"
+    );
+    assert_eq!(
+        convert("k'ung1 hsüeh2 ssu4", PinyinStyle::Pinyin),
+        "kōng xué sì"
+    );
+    // `kuo2` is valid pinyin too, so the style has to be given
+    assert_eq!(
+        romanize(
+            "k'ung1 chih1 kuo2 jên2",
+            Some(PinyinStyle::WadeGiles),
+            PinyinStyle::Pinyin
+        ),
+        "kōng zhī guó rén"
+    );
+    assert_eq!(
+        convert(
+            "mā má hǎo xiǎo yǒu wǒ yǔ shuǐ qù huàng",
+            PinyinStyle::GwoyeuRomatzyh
+        ),
+        "mha ma hao sheau yeou woo yeu shoei chiuh huanq"
+    );
+    assert_eq!(
+        convert("guó yán yǐ yě ér zhí qián", PinyinStyle::GwoyeuRomatzyh),
+        "gwo yan yii yee erl jyr chyan"
+    );
+    assert_eq!(
+        convert("yī wēn wén yè wù", PinyinStyle::GwoyeuRomatzyh),
+        "i uen wen yeh wuh"
+    );
+    assert_eq!(
+        romanize(
+            "sheau yeou",
+            Some(PinyinStyle::GwoyeuRomatzyh),
+            PinyinStyle::Pinyin
+        ),
+        "xiǎo yǒu"
+    );
+    assert_eq!(convert("（語音）wǒ", PinyinStyle::Numbered), "（語音）wo3");
+    assert_eq!(convert("空穴 lái", PinyinStyle::Numbered), "空穴 lai2");
+}
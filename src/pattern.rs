@@ -0,0 +1,243 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+
+/// Headword pattern, either a glob or, once it uses `^` or `$`, a small
+/// regular expression.
+///
+/// - Glob: `?` is any character, `*` any run of characters, and the pattern
+///   has to match the whole headword. Text without wildcards matches
+///   headwords containing it.
+/// - Regex: `.`, `[...]` and `[^...]` match one character, followed by an
+///   optional `?`, `*`, `+`, `{n}`, `{m,}` or `{m,n}`. `^` and `$` anchor it.
+///
+/// `.` and `{...}` also work in globs, and `\` escapes the next character.
+/// Classes list characters literally without ranges, so `[a-c]` matches `a`,
+/// `-` or `c`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadwordPattern {
+    tokens: Vec<PatternToken>,
+    anchored_start: bool,
+    anchored_end: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PatternToken {
+    atom: PatternAtom,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternAtom {
+    Char(char),
+    Any,
+    Class { chars: Vec<char>, negated: bool },
+}
+
+impl PatternAtom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            PatternAtom::Char(x) => *x == c,
+            PatternAtom::Any => true,
+            PatternAtom::Class { chars, negated } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+impl FromStr for HeadwordPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invaild = || anyhow!("Invaild pattern: {}", s);
+        let mut chars = s.chars().collect::<Vec<_>>();
+        let anchored_start = chars.first() == Some(&'^');
+        // `\$` is a literal `$`, but `\\$` an escaped `\` before the anchor
+        let escapes = chars
+            .iter()
+            .rev()
+            .skip(1)
+            .take_while(|x| **x == '\\')
+            .count();
+        let anchored_end = chars.last() == Some(&'$') && escapes % 2 == 0;
+        let is_regex = anchored_start || anchored_end;
+        if anchored_end {
+            chars.pop();
+        }
+        if anchored_start {
+            chars.remove(0);
+        }
+
+        let mut tokens: Vec<PatternToken> = Vec::new();
+        let mut is_plain = true;
+        let mut index = 0;
+        while index < chars.len() {
+            let c = chars[index];
+            index += 1;
+            let quantifier = match c {
+                '?' | '*' | '+' if is_regex => Some(match c {
+                    '?' => (0, Some(1)),
+                    '*' => (0, None),
+                    _ => (1, None),
+                }),
+                '{' => {
+                    let end = chars[index..]
+                        .iter()
+                        .position(|x| *x == '}')
+                        .ok_or_else(invaild)?;
+                    let range = chars[index..index + end].iter().collect::<String>();
+                    index += end + 1;
+                    Some(parse_repeat(&range).ok_or_else(invaild)?)
+                }
+                _ => None,
+            };
+            if let Some((min, max)) = quantifier {
+                let token = tokens.last_mut().ok_or_else(invaild)?;
+                if token.min != 1 || token.max != Some(1) {
+                    return Err(invaild());
+                }
+                token.min = min;
+                token.max = max;
+                is_plain = false;
+                continue;
+            }
+
+            let (atom, min, max) = match c {
+                '?' => (PatternAtom::Any, 1, Some(1)),
+                '*' => (PatternAtom::Any, 0, None),
+                '.' => (PatternAtom::Any, 1, Some(1)),
+                '[' => {
+                    let end = chars[index..]
+                        .iter()
+                        .position(|x| *x == ']')
+                        .ok_or_else(invaild)?;
+                    let mut class = chars[index..index + end].to_vec();
+                    index += end + 1;
+                    let negated = class.first() == Some(&'^');
+                    if negated {
+                        class.remove(0);
+                    }
+                    if class.is_empty() {
+                        return Err(invaild());
+                    }
+                    (
+                        PatternAtom::Class {
+                            chars: class,
+                            negated,
+                        },
+                        1,
+                        Some(1),
+                    )
+                }
+                '\\' => {
+                    let c = *chars.get(index).ok_or_else(invaild)?;
+                    index += 1;
+                    tokens.push(PatternToken {
+                        atom: PatternAtom::Char(c),
+                        min: 1,
+                        max: Some(1),
+                    });
+                    continue;
+                }
+                c => {
+                    tokens.push(PatternToken {
+                        atom: PatternAtom::Char(c),
+                        min: 1,
+                        max: Some(1),
+                    });
+                    continue;
+                }
+            };
+            is_plain = false;
+            tokens.push(PatternToken { atom, min, max });
+        }
+
+        // Globs match whole headwords, plain text anywhere in them
+        let anchored = !is_regex && !is_plain;
+
+        Ok(HeadwordPattern {
+            tokens,
+            anchored_start: anchored_start || anchored,
+            anchored_end: anchored_end || anchored,
+        })
+    }
+}
+
+/// `n`, `m,` or `m,n` of a `{...}` repetition
+fn parse_repeat(s: &str) -> Option<(usize, Option<usize>)> {
+    let (min, max) = match s.split_once(',') {
+        Some((min, "")) => (min.trim().parse().ok()?, None),
+        Some((min, max)) => (min.trim().parse().ok()?, Some(max.trim().parse().ok()?)),
+        None => {
+            let n = s.trim().parse().ok()?;
+            (n, Some(n))
+        }
+    };
+    if max.is_some_and(|max| max < min) {
+        return None;
+    }
+
+    Some((min, max))
+}
+
+impl HeadwordPattern {
+    pub fn is_match(&self, word: &str) -> bool {
+        let chars = word.chars().collect::<Vec<_>>();
+        if self.anchored_start {
+            return self.match_here(&self.tokens, &chars);
+        }
+
+        (0..=chars.len()).any(|start| self.match_here(&self.tokens, &chars[start..]))
+    }
+
+    /// Greedy backtracking, which is plenty for headwords of a few characters
+    fn match_here(&self, tokens: &[PatternToken], chars: &[char]) -> bool {
+        let (token, rest) = match tokens.split_first() {
+            Some(x) => x,
+            None => return !self.anchored_end || chars.is_empty(),
+        };
+        let max = token.max.unwrap_or(chars.len()).min(chars.len());
+        let count = chars[..max]
+            .iter()
+            .take_while(|x| token.atom.matches(**x))
+            .count();
+        if count < token.min {
+            return false;
+        }
+
+        (token.min..=count)
+            .rev()
+            .any(|n| self.match_here(rest, &chars[n..]))
+    }
+}
+
+#[test]
+fn test_headword_pattern() {
+    let is_match =
+        |pattern: &str, word: &str| pattern.parse::<HeadwordPattern>().unwrap().is_match(word);
+
+    assert!(is_match("空?來風", "空穴來風"));
+    assert!(!is_match("空?來風", "空來風"));
+    assert!(is_match("*來風", "空穴來風"));
+    assert!(!is_match("*來風", "來風去"));
+    assert!(is_match("來風", "空穴來風"));
+    assert!(is_match("^一.{3}$", "一心一意"));
+    assert!(!is_match("^一.{3}$", "一心"));
+    assert!(is_match("^一", "一心"));
+    assert!(!is_match("^一", "心一"));
+    assert!(is_match("風$", "來風"));
+    assert!(is_match("^[一二]?心+$", "心心"));
+    assert!(is_match("^[^一].{1,2}$", "二心"));
+    assert!(!is_match("^[^一].{1,2}$", "一心"));
+    assert!(is_match("?{2}風", "來來風"));
+    assert!(is_match("^a\\.b$", "a.b"));
+    assert!(!is_match("^a\\.b$", "acb"));
+    assert!(is_match("^a\\\\$", "a\\"));
+    assert!(!is_match("^a\\\\$", "a\\b"));
+    assert!(is_match("^a\\$", "a$"));
+    assert!(is_match("^[a-c]$", "-"));
+    assert!(!is_match("^[a-c]$", "b"));
+    assert!("{2}".parse::<HeadwordPattern>().is_err());
+    assert!("^一.{3,1}$".parse::<HeadwordPattern>().is_err());
+    assert!("[一".parse::<HeadwordPattern>().is_err());
+}
@@ -0,0 +1,373 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Error, Result};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use serde_json::Value;
+
+use crate::api::MoedictDictionary;
+use crate::cache::{temp_path, CACHE_PATH_DIRECTORY};
+
+const TABLE_MAGIC: &[u8; 8] = b"MEOWTBL1";
+const TABLE_HEADER_LENGTH: u64 = 16;
+const TABLE_RECORD_LENGTH: u64 = 24;
+
+/// Read-only string table sorted by key, answering single-key lookups with a
+/// binary search over fixed-size records instead of parsing the whole file
+///
+/// Layout: magic, entry count, then `count` records of
+/// `(key_offset: u64, key_len: u32, value_offset: u64, value_len: u32)`,
+/// then the keys blob and the values blob. All integers are little endian.
+pub struct SortedTable {
+    file: File,
+    count: u64,
+}
+
+struct TableRecord {
+    key_offset: u64,
+    key_len: u32,
+    value_offset: u64,
+    value_len: u32,
+}
+
+impl TableRecord {
+    fn from_bytes(buf: &[u8]) -> Self {
+        TableRecord {
+            key_offset: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            key_len: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+            value_offset: u64::from_le_bytes(buf[12..20].try_into().unwrap()),
+            value_len: u32::from_le_bytes(buf[20..24].try_into().unwrap()),
+        }
+    }
+}
+
+impl SortedTable {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; TABLE_HEADER_LENGTH as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| anyhow!("Invaild table file: {}", path.display()))?;
+        if &header[..8] != TABLE_MAGIC {
+            return Err(anyhow!("Invaild table file: {}", path.display()));
+        }
+        let count = u64::from_le_bytes(header[8..].try_into().unwrap());
+        let table = SortedTable { file, count };
+        // Values are written last, so a truncated file ends before the last value
+        let expected_length = match count {
+            0 => TABLE_HEADER_LENGTH,
+            _ => {
+                let record = table
+                    .read_record(count - 1)
+                    .map_err(|_| anyhow!("Truncated table file: {}", path.display()))?;
+                record.value_offset + record.value_len as u64
+            }
+        };
+        if table.file.metadata()?.len() != expected_length {
+            return Err(anyhow!("Truncated table file: {}", path.display()));
+        }
+
+        Ok(table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let record = self.read_record(mid)?;
+            let record_key = self.read_bytes(record.key_offset, record.key_len)?;
+            match record_key.as_slice().cmp(key.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    let value = self.read_bytes(record.value_offset, record.value_len)?;
+                    return Ok(Some(String::from_utf8(value)?));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let records = self.records()?;
+        let keys_start = match records.first() {
+            Some(record) => record.key_offset,
+            None => return Ok(Vec::new()),
+        };
+        let keys_length = records
+            .iter()
+            .map(|x| x.key_len as u64)
+            .sum::<u64>()
+            .try_into()?;
+        let keys = self.read_bytes(keys_start, keys_length)?;
+
+        records
+            .iter()
+            .map(|x| {
+                let start = (x.key_offset - keys_start) as usize;
+                Ok(std::str::from_utf8(&keys[start..start + x.key_len as usize])?.to_string())
+            })
+            .collect()
+    }
+
+    /// Every `(key, value)` pair, reading the values blob in one go
+    pub fn entries(&self) -> Result<Vec<(String, String)>> {
+        let keys = self.keys()?;
+        let records = self.records()?;
+        let (first, last) = match (records.first(), records.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(Vec::new()),
+        };
+        let values_start = first.value_offset;
+        let values = self.read_bytes(
+            values_start,
+            (last.value_offset + last.value_len as u64 - values_start).try_into()?,
+        )?;
+
+        keys.into_iter()
+            .zip(&records)
+            .map(|(key, x)| {
+                let start = (x.value_offset - values_start) as usize;
+                let value = std::str::from_utf8(&values[start..start + x.value_len as usize])?;
+                Ok((key, value.to_string()))
+            })
+            .collect()
+    }
+
+    fn records(&self) -> Result<Vec<TableRecord>> {
+        let records = self.read_bytes(
+            TABLE_HEADER_LENGTH,
+            (self.count * TABLE_RECORD_LENGTH).try_into()?,
+        )?;
+
+        Ok(records
+            .chunks(TABLE_RECORD_LENGTH as usize)
+            .map(TableRecord::from_bytes)
+            .collect())
+    }
+
+    fn read_record(&self, index: u64) -> Result<TableRecord> {
+        let buf = self.read_bytes(
+            TABLE_HEADER_LENGTH + index * TABLE_RECORD_LENGTH,
+            TABLE_RECORD_LENGTH as u32,
+        )?;
+
+        Ok(TableRecord::from_bytes(&buf))
+    }
+
+    fn read_bytes(&self, offset: u64, length: u32) -> Result<Vec<u8>> {
+        let mut file = &self.file;
+        let mut buf = vec![0u8; length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+}
+
+/// Write `entries` as a `SortedTable`, later duplicates replacing earlier ones,
+/// and return the number of entries written
+pub fn write_sorted_table(path: &Path, mut entries: Vec<(String, String)>) -> Result<usize> {
+    entries.reverse();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+
+    let tmp_path = temp_path(path);
+    let result = write_table_file(&tmp_path, &entries)
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(Error::from));
+    if let Err(e) = result {
+        fs::remove_file(&tmp_path).ok();
+        return Err(e);
+    }
+
+    Ok(entries.len())
+}
+
+fn write_table_file(path: &Path, entries: &[(String, String)]) -> Result<()> {
+    let count = entries.len() as u64;
+    let keys_start = TABLE_HEADER_LENGTH + count * TABLE_RECORD_LENGTH;
+    let values_start = keys_start + entries.iter().map(|x| x.0.len() as u64).sum::<u64>();
+    let mut f = BufWriter::new(File::create(path)?);
+    f.write_all(TABLE_MAGIC)?;
+    f.write_all(&count.to_le_bytes())?;
+    let (mut key_offset, mut value_offset) = (keys_start, values_start);
+    for (key, value) in entries {
+        f.write_all(&key_offset.to_le_bytes())?;
+        f.write_all(&u32::try_from(key.len())?.to_le_bytes())?;
+        f.write_all(&value_offset.to_le_bytes())?;
+        f.write_all(&u32::try_from(value.len())?.to_le_bytes())?;
+        key_offset += key.len() as u64;
+        value_offset += value.len() as u64;
+    }
+    for (key, _) in entries {
+        f.write_all(key.as_bytes())?;
+    }
+    for (_, value) in entries {
+        f.write_all(value.as_bytes())?;
+    }
+    f.into_inner()?.sync_all()?;
+
+    Ok(())
+}
+
+/// Import a moedict JSON dump (an array of entries) into the offline store
+/// and return the number of imported entries
+pub fn import_moedict_dump(dump_path: &Path, store_path: &Path) -> Result<usize> {
+    let reader = BufReader::new(File::open(dump_path)?);
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let entries = deserializer.deserialize_seq(MoedictDumpVisitor)?;
+    if let Some(parent) = store_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    write_sorted_table(store_path, entries)
+}
+
+pub fn moedict_store_path(dict: MoedictDictionary) -> PathBuf {
+    match dict {
+        MoedictDictionary::Mandarin => CACHE_PATH_DIRECTORY.join("moedict_offline.db"),
+        _ => CACHE_PATH_DIRECTORY.join(format!("moedict_offline_{}.db", dict.path())),
+    }
+}
+
+/// Look up the raw moedict entry of `keyword` in the offline store, if any
+pub fn get_offline_moedict(keyword: &str, dict: MoedictDictionary) -> Result<Option<String>> {
+    let store_path = moedict_store_path(dict);
+    if !store_path.exists() {
+        return Ok(None);
+    }
+
+    SortedTable::open(&store_path)?.get(keyword)
+}
+
+/// All headwords in the offline store, if it exists
+pub fn get_offline_moedict_index(dict: MoedictDictionary) -> Result<Option<Vec<String>>> {
+    let store_path = moedict_store_path(dict);
+    if !store_path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(SortedTable::open(&store_path)?.keys()?))
+}
+
+/// Raw entries of every single character headword in the offline store, if it exists
+pub fn get_offline_moedict_characters(
+    dict: MoedictDictionary,
+) -> Result<Option<Vec<(String, String)>>> {
+    let store_path = moedict_store_path(dict);
+    if !store_path.exists() {
+        return Ok(None);
+    }
+    let entries = SortedTable::open(&store_path)?
+        .entries()?
+        .into_iter()
+        .filter(|(key, _)| key.chars().count() == 1)
+        .collect();
+
+    Ok(Some(entries))
+}
+
+/// Collects `(title, compact entry json)` pairs one element at a time, so the
+/// dump never has to be held as a whole `Value` tree
+struct MoedictDumpVisitor;
+
+impl<'de> Visitor<'de> for MoedictDumpVisitor {
+    type Value = Vec<(String, String)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of moedict entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(entry) = seq.next_element::<Value>()? {
+            let title = entry
+                .get("t")
+                .or_else(|| entry.get("title"))
+                .and_then(|x| x.as_str())
+                .map(|x| x.replace(['`', '~'], ""));
+            if let Some(title) = title {
+                entries.push((title, entry.to_string()));
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+#[test]
+fn test_sorted_table() {
+    use tempfile::NamedTempFile;
+    let file = NamedTempFile::new().unwrap();
+    let entries = vec![
+        ("我".to_string(), "ngo5".to_string()),
+        ("你".to_string(), "nei5".to_string()),
+        ("我哋".to_string(), "ngo5 dei6".to_string()),
+        ("我".to_string(), "ngo5!".to_string()),
+    ];
+    let count = write_sorted_table(file.path(), entries).unwrap();
+    let table = SortedTable::open(file.path()).unwrap();
+
+    assert_eq!(count, 3);
+    assert_eq!(table.get("我").unwrap(), Some("ngo5!".to_string()));
+    assert_eq!(table.get("我哋").unwrap(), Some("ngo5 dei6".to_string()));
+    assert_eq!(table.get("佢").unwrap(), None);
+    assert_eq!(table.keys().unwrap(), vec!["你", "我", "我哋"]);
+    assert_eq!(
+        table.entries().unwrap(),
+        vec![
+            ("你".to_string(), "nei5".to_string()),
+            ("我".to_string(), "ngo5!".to_string()),
+            ("我哋".to_string(), "ngo5 dei6".to_string()),
+        ]
+    );
+    let truncated_length = fs::metadata(file.path()).unwrap().len() - 1;
+    File::options()
+        .write(true)
+        .open(file.path())
+        .unwrap()
+        .set_len(truncated_length)
+        .unwrap();
+    assert!(SortedTable::open(file.path()).is_err());
+}
+
+#[test]
+fn test_sorted_table_cleanup() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.db");
+    fs::create_dir(&path).unwrap();
+
+    assert!(write_sorted_table(&path, vec![("我".to_string(), "ngo5".to_string())]).is_err());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_import_moedict_dump() {
+    use tempfile::tempdir;
+    let dir = tempdir().unwrap();
+    let dump_path = dir.path().join("dump.json");
+    let store_path = dir.path().join("store.db");
+    fs::write(
+        &dump_path,
+        r#"[{"t":"`我~","h":[{"p":"wǒ","d":[{"f":"自稱。"}]}]},{"title":"你","heteronyms":[]},{"t":"你","h":[]}]"#,
+    )
+    .unwrap();
+    let count = import_moedict_dump(&dump_path, &store_path).unwrap();
+    let table = SortedTable::open(&store_path).unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(table.keys().unwrap(), vec!["你", "我"]);
+    assert_eq!(
+        table.get("我").unwrap(),
+        Some(r#"{"h":[{"d":[{"f":"自稱。"}],"p":"wǒ"}],"t":"`我~"}"#.to_string())
+    );
+}